@@ -0,0 +1,397 @@
+//! Distributed work offloading for `ThreadPool`: dispatch jobs to worker
+//! processes on other machines over a small length-prefixed TCP protocol,
+//! inspired by the distributed-controller pattern. Each remote node runs
+//! its own local, `Injector`-based `ThreadPool` as the actual execution
+//! backend — this module is only the wire protocol and job-id routing glue
+//! connecting a coordinator to those workers, so a `par_map` workload can
+//! scale horizontally across a cluster with the same surface as the local
+//! `ThreadPool::par_map`.
+
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::marker::PhantomData;
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+
+use log::error;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::thread_pool::ThreadPool;
+
+/// Frame header: `[type: u8][job_id: u64 LE][payload_len: u64 LE]`.
+const HEADER_LEN: usize = 1 + 8 + 8;
+
+/// Cap on a single frame's declared payload length. `payload_len` comes
+/// straight off the wire from a peer, so without a cap a malformed or
+/// malicious 8-byte length field would make `read_from` try to allocate an
+/// attacker-chosen (up to exabyte-scale) buffer and abort the process.
+const MAX_FRAME_PAYLOAD_LEN: u64 = 256 * 1024 * 1024;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum FrameType {
+    Job = 0,
+    Result = 1,
+}
+impl FrameType {
+    fn from_u8(byte: u8) -> io::Result<FrameType> {
+        match byte {
+            0 => Ok(FrameType::Job),
+            1 => Ok(FrameType::Result),
+            other => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unknown distributed pool frame type: {}", other),
+            )),
+        }
+    }
+}
+
+struct Frame {
+    kind: FrameType,
+    job_id: u64,
+    payload: Vec<u8>,
+}
+impl Frame {
+    fn write_to<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        let mut header = [0u8; HEADER_LEN];
+        header[0] = self.kind as u8;
+        header[1..9].copy_from_slice(&self.job_id.to_le_bytes());
+        header[9..17].copy_from_slice(&(self.payload.len() as u64).to_le_bytes());
+        w.write_all(&header)?;
+        w.write_all(&self.payload)?;
+        w.flush()
+    }
+
+    fn read_from<R: Read>(r: &mut R) -> io::Result<Frame> {
+        let mut header = [0u8; HEADER_LEN];
+        r.read_exact(&mut header)?;
+        let kind = FrameType::from_u8(header[0])?;
+        let job_id = u64::from_le_bytes(header[1..9].try_into().unwrap());
+        let payload_len = u64::from_le_bytes(header[9..17].try_into().unwrap());
+        if payload_len > MAX_FRAME_PAYLOAD_LEN {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "distributed pool frame payload_len {} exceeds max {}",
+                    payload_len, MAX_FRAME_PAYLOAD_LEN
+                ),
+            ));
+        }
+        let mut payload = vec![0u8; payload_len as usize];
+        r.read_exact(&mut payload)?;
+        Ok(Frame {
+            kind,
+            job_id,
+            payload,
+        })
+    }
+}
+
+/// Returned by `Codec::decode` when a frame's payload doesn't decode to the
+/// expected type — e.g. a corrupt frame, or a peer running a mismatched
+/// version of the job/output types. Unlike `encode` (which only ever
+/// serializes values this process produced itself), `decode` reads bytes
+/// that came straight off the network, so a malformed payload is an
+/// expected failure mode to report, not a bug to panic on.
+#[derive(Debug)]
+pub struct DecodeError(String);
+impl std::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "failed to decode distributed job payload: {}", self.0)
+    }
+}
+impl std::error::Error for DecodeError {}
+
+/// Encodes job input/output across the wire. A `serde_json`-based
+/// implementation is provided as `JsonCodec`; implement this trait
+/// directly (e.g. over `bincode`) for a more compact wire format.
+pub trait Codec<T>: Send + Sync {
+    fn encode(&self, value: &T) -> Vec<u8>;
+    fn decode(&self, bytes: &[u8]) -> Result<T, DecodeError>;
+}
+
+/// `Codec` backed by `serde_json`.
+pub struct JsonCodec;
+impl<T: Serialize + DeserializeOwned> Codec<T> for JsonCodec {
+    fn encode(&self, value: &T) -> Vec<u8> {
+        serde_json::to_vec(value).expect("failed to encode distributed job payload")
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<T, DecodeError> {
+        serde_json::from_slice(bytes).map_err(|e| DecodeError(e.to_string()))
+    }
+}
+
+/// Error returned by `DistributedPool::submit` when the connection it was
+/// routed to closes before a result frame for it comes back — e.g. a dead
+/// remote worker — instead of blocking the caller forever.
+#[derive(Debug)]
+pub struct Disconnected;
+
+impl std::fmt::Display for Disconnected {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "distributed pool connection closed before a result arrived"
+        )
+    }
+}
+
+impl std::error::Error for Disconnected {}
+
+type ResultSlot<Out> = Arc<(Mutex<Option<Result<Out, Disconnected>>>, Condvar)>;
+
+/// Coordinator side: dispatches job inputs to a set of remote worker nodes
+/// over TCP and routes their results back to the caller by job id.
+///
+/// `In`/`Out` are the input/output types of whatever single computation
+/// this pool's workers were started to perform (see `DistributedWorker`);
+/// a cluster running several distinct computations uses one
+/// `DistributedPool` per computation, each against its own listener port.
+pub struct DistributedPool<In, Out, C>
+where
+    C: Codec<In> + Codec<Out>,
+{
+    codec: Arc<C>,
+    streams: Vec<Mutex<TcpStream>>,
+    next_stream: AtomicUsize,
+    next_job_id: AtomicU64,
+    // Keyed by job id; the `usize` is the stream index the job was sent on,
+    // so `route_results` can fail only the entries orphaned by its own
+    // connection dropping, not every in-flight job across the whole pool.
+    pending: Arc<Mutex<HashMap<u64, (usize, ResultSlot<Out>)>>>,
+    _marker: PhantomData<fn(In) -> Out>,
+}
+
+impl<In, Out, C> DistributedPool<In, Out, C>
+where
+    In: Send + 'static,
+    Out: Send + 'static,
+    C: Codec<In> + Codec<Out> + 'static,
+{
+    /// Connect to every worker node in `addrs` and start routing results
+    /// back as they arrive.
+    pub fn connect<A: ToSocketAddrs>(addrs: &[A], codec: C) -> io::Result<Self> {
+        let codec = Arc::new(codec);
+        let pending: Arc<Mutex<HashMap<u64, (usize, ResultSlot<Out>)>>> =
+            Arc::new(Mutex::new(HashMap::new()));
+        let mut streams = Vec::with_capacity(addrs.len());
+
+        for (stream_idx, addr) in addrs.iter().enumerate() {
+            let stream = TcpStream::connect(addr)?;
+            let reader_stream = stream.try_clone()?;
+            let pending = Arc::clone(&pending);
+            let codec = Arc::clone(&codec);
+
+            thread::spawn(move || Self::route_results(stream_idx, reader_stream, pending, codec));
+
+            streams.push(Mutex::new(stream));
+        }
+
+        Ok(DistributedPool {
+            codec,
+            streams,
+            next_stream: AtomicUsize::new(0),
+            next_job_id: AtomicU64::new(0),
+            pending,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Fail every job still `pending` on `stream_idx` with `Disconnected`,
+    /// so none of their `submit` callers are left blocked forever.
+    fn fail_pending(stream_idx: usize, pending: &Mutex<HashMap<u64, (usize, ResultSlot<Out>)>>) {
+        let mut pending = pending.lock().unwrap();
+        let orphaned: Vec<u64> = pending
+            .iter()
+            .filter(|(_, (idx, _))| *idx == stream_idx)
+            .map(|(job_id, _)| *job_id)
+            .collect();
+        for job_id in orphaned {
+            if let Some((_, slot)) = pending.remove(&job_id) {
+                let (lock, cvar) = &*slot;
+                *lock.lock().unwrap() = Some(Err(Disconnected));
+                cvar.notify_all();
+            }
+        }
+    }
+
+    /// Read `Result` frames off `stream` until it closes, handing each
+    /// decoded output to the caller blocked in `submit` on that job id.
+    ///
+    /// When the connection closes, every job still `pending` on this same
+    /// `stream_idx` is failed with `Disconnected` instead of being left to
+    /// block its caller in `submit` forever — a dead remote worker would
+    /// otherwise wedge the coordinator on every job routed to it.
+    fn route_results(
+        stream_idx: usize,
+        mut stream: TcpStream,
+        pending: Arc<Mutex<HashMap<u64, (usize, ResultSlot<Out>)>>>,
+        codec: Arc<C>,
+    ) {
+        loop {
+            let frame = match Frame::read_from(&mut stream) {
+                Ok(frame) => frame,
+                Err(_) => {
+                    Self::fail_pending(stream_idx, &pending);
+                    return;
+                }
+            };
+            if frame.kind != FrameType::Result {
+                continue;
+            }
+            let slot = pending.lock().unwrap().remove(&frame.job_id);
+            if let Some((_, slot)) = slot {
+                let output = match codec.decode(&frame.payload) {
+                    Ok(output) => output,
+                    Err(e) => {
+                        error!("dropping distributed worker connection: {}", e);
+                        Self::fail_pending(stream_idx, &pending);
+                        return;
+                    }
+                };
+                let (lock, cvar) = &*slot;
+                *lock.lock().unwrap() = Some(Ok(output));
+                cvar.notify_all();
+            }
+        }
+    }
+
+    /// Dispatch `input` to a worker node (round-robin across connections)
+    /// and block until its result comes back, or the connection it was
+    /// routed to drops first.
+    pub fn submit(&self, input: In) -> Result<Out, Disconnected> {
+        let job_id = self.next_job_id.fetch_add(1, Ordering::Relaxed);
+        let payload = self.codec.encode(&input);
+
+        let stream_idx = self.next_stream.fetch_add(1, Ordering::Relaxed) % self.streams.len();
+        let slot: ResultSlot<Out> = Arc::new((Mutex::new(None), Condvar::new()));
+        self.pending
+            .lock()
+            .unwrap()
+            .insert(job_id, (stream_idx, Arc::clone(&slot)));
+
+        let frame = Frame {
+            kind: FrameType::Job,
+            job_id,
+            payload,
+        };
+        if frame
+            .write_to(&mut *self.streams[stream_idx].lock().unwrap())
+            .is_err()
+        {
+            self.pending.lock().unwrap().remove(&job_id);
+            return Err(Disconnected);
+        }
+
+        let (lock, cvar) = &*slot;
+        let guard = lock.lock().unwrap();
+        let mut guard = cvar.wait_while(guard, |result| result.is_none()).unwrap();
+        guard.take().expect("result slot signaled with no value")
+    }
+
+    /// Distribute `inputs` across the cluster and collect their outputs in
+    /// the same order, mirroring `ThreadPool::par_map`'s surface.
+    pub fn par_map<I>(&self, inputs: I) -> Vec<Result<Out, Disconnected>>
+    where
+        I: IntoIterator<Item = In>,
+    {
+        thread::scope(|scope| {
+            inputs
+                .into_iter()
+                .map(|input| scope.spawn(|| self.submit(input)))
+                .collect::<Vec<_>>()
+                .into_iter()
+                .map(|handle| handle.join().expect("remote job thread panicked"))
+                .collect()
+        })
+    }
+}
+
+/// Worker side: listens for job frames, runs each decoded input through
+/// `handler` on a local `ThreadPool`, and writes the encoded result back.
+pub struct DistributedWorker<In, Out, C, F>
+where
+    In: Send + 'static,
+    Out: Send + 'static,
+    C: Codec<In> + Codec<Out> + Send + Sync + 'static,
+    F: Fn(In) -> Out + Send + Sync + 'static,
+{
+    codec: Arc<C>,
+    handler: Arc<F>,
+    pool: Arc<ThreadPool>,
+    _marker: PhantomData<fn(In) -> Out>,
+}
+
+impl<In, Out, C, F> DistributedWorker<In, Out, C, F>
+where
+    In: Send + 'static,
+    Out: Send + 'static,
+    C: Codec<In> + Codec<Out> + Send + Sync + 'static,
+    F: Fn(In) -> Out + Send + Sync + 'static,
+{
+    pub fn new(pool: Arc<ThreadPool>, codec: C, handler: F) -> Self {
+        DistributedWorker {
+            codec: Arc::new(codec),
+            handler: Arc::new(handler),
+            pool,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Bind `addr` and serve connections until the process is killed. Each
+    /// connection gets its own reader thread; decoded jobs are handed to
+    /// the local `ThreadPool::execute` so one slow job can't block another
+    /// connection's jobs.
+    pub fn serve<A: ToSocketAddrs>(self, addr: A) -> io::Result<()> {
+        let listener = TcpListener::bind(addr)?;
+        let this = Arc::new(self);
+
+        for stream in listener.incoming() {
+            let stream = stream?;
+            let this = Arc::clone(&this);
+            thread::spawn(move || this.serve_connection(stream));
+        }
+        Ok(())
+    }
+
+    fn serve_connection(&self, mut stream: TcpStream) {
+        loop {
+            let frame = match Frame::read_from(&mut stream) {
+                Ok(frame) => frame,
+                Err(_) => return,
+            };
+            if frame.kind != FrameType::Job {
+                continue;
+            }
+
+            let codec = Arc::clone(&self.codec);
+            let handler = Arc::clone(&self.handler);
+            let job_id = frame.job_id;
+            let input = match codec.decode(&frame.payload) {
+                Ok(input) => input,
+                Err(e) => {
+                    error!("dropping distributed worker connection: {}", e);
+                    return;
+                }
+            };
+            let mut reply_stream = match stream.try_clone() {
+                Ok(s) => s,
+                Err(_) => return,
+            };
+
+            self.pool.execute(move || {
+                let output = handler(input);
+                let payload = codec.encode(&output);
+                let frame = Frame {
+                    kind: FrameType::Result,
+                    job_id,
+                    payload,
+                };
+                let _ = frame.write_to(&mut reply_stream);
+            });
+        }
+    }
+}
@@ -12,5 +12,7 @@ pub mod map;
 pub mod core;
 pub mod pipeline;
 pub mod pspp;
+pub mod templates;
 mod task;
 pub mod thread_pool;
+pub mod distributed;
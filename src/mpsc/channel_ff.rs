@@ -1,18 +1,67 @@
 use ff_buffer::{self, FFReceiver, FFSender};
-use std::sync::Mutex;
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc, Condvar, Mutex,
+};
 
 use super::{
     channel::{Receiver, Sender},
     err::ChannelError,
 };
 
+/// Shared depth tracking for a bounded channel. `FFSender::push` itself is
+/// non-blocking and the underlying fastflow buffer is unbounded, so
+/// backpressure is layered on top: `send` increments `depth` and parks on
+/// `cvar` whenever that would push it past `capacity`; `receive`/`pop`
+/// decrement `depth` and wake a parked sender.
+struct Backpressure {
+    capacity: usize,
+    depth: AtomicUsize,
+    lock: Mutex<()>,
+    cvar: Condvar,
+}
+impl Backpressure {
+    fn new(capacity: usize) -> Backpressure {
+        Backpressure {
+            capacity,
+            depth: AtomicUsize::new(0),
+            lock: Mutex::new(()),
+            cvar: Condvar::new(),
+        }
+    }
+
+    /// Block until there is room for one more in-flight message, then
+    /// reserve it.
+    fn acquire(&self) {
+        let guard = self.lock.lock().unwrap();
+        let _guard = self
+            .cvar
+            .wait_while(guard, |_| self.depth.load(Ordering::Acquire) >= self.capacity)
+            .unwrap();
+        self.depth.fetch_add(1, Ordering::AcqRel);
+    }
+
+    /// Release a slot reserved by `acquire`, waking a parked sender.
+    fn release(&self) {
+        self.depth.fetch_sub(1, Ordering::AcqRel);
+        let _guard = self.lock.lock().unwrap();
+        self.cvar.notify_one();
+    }
+}
+
 pub struct FFInputChannel<T> {
     rx: FFReceiver<T>,
+    backpressure: Option<Arc<Backpressure>>,
 }
 impl<T: Send> Receiver<T> for FFInputChannel<T> {
     fn receive(&self) -> Result<Option<T>, ChannelError> {
         match self.rx.try_pop() {
-            Some(boxed) => Ok(Some(Box::into_inner(boxed))),
+            Some(boxed) => {
+                if let Some(bp) = &self.backpressure {
+                    bp.release();
+                }
+                Ok(Some(Box::into_inner(boxed)))
+            }
             None => {
                 if self.rx.is_disconnected() {
                     Err(ChannelError::new("Can't receive the msg."))
@@ -30,11 +79,17 @@ impl<T: Send> Receiver<T> for FFInputChannel<T> {
 
 pub struct FFBlockingInputChannel<T> {
     rx: FFReceiver<T>,
+    backpressure: Option<Arc<Backpressure>>,
 }
 impl<T: Send> Receiver<T> for FFBlockingInputChannel<T> {
     fn receive(&self) -> Result<Option<T>, ChannelError> {
         match self.rx.pop() {
-            Some(boxed) => Ok(Some(Box::into_inner(boxed))),
+            Some(boxed) => {
+                if let Some(bp) = &self.backpressure {
+                    bp.release();
+                }
+                Ok(Some(Box::into_inner(boxed)))
+            }
             None => Err(ChannelError::new("Can't receive the msg.")),
         }
     }
@@ -46,10 +101,14 @@ impl<T: Send> Receiver<T> for FFBlockingInputChannel<T> {
 
 pub struct FFOutputChannel<T> {
     tx: Mutex<FFSender<T>>,
+    backpressure: Option<Arc<Backpressure>>,
 }
 
 impl<T: Send> Sender<T> for FFOutputChannel<T> {
     fn send(&self, msg: T) -> Result<(), ChannelError> {
+        if let Some(bp) = &self.backpressure {
+            bp.acquire();
+        }
         let mtx = self.tx.lock();
         match mtx {
             Ok(ch) => {
@@ -78,13 +137,52 @@ impl Channel {
         let (tx, rx) = ff_buffer::build::<T>();
         if blocking {
             (
-                Box::new(FFBlockingInputChannel { rx }),
-                Box::new(FFOutputChannel { tx: Mutex::new(tx) }),
+                Box::new(FFBlockingInputChannel { rx, backpressure: None }),
+                Box::new(FFOutputChannel { tx: Mutex::new(tx), backpressure: None }),
+            )
+        } else {
+            (
+                Box::new(FFInputChannel { rx, backpressure: None }),
+                Box::new(FFOutputChannel { tx: Mutex::new(tx), backpressure: None }),
+            )
+        }
+    }
+
+    /// Like `channel`, but `send` blocks (parking on a condvar) once
+    /// `capacity` messages are in flight and haven't been drained by the
+    /// receiver yet, rather than enqueueing unboundedly. Use this when a
+    /// fast source feeds a slow stage and unbounded queue growth would be a
+    /// problem for a long-running streaming pipeline.
+    pub fn bounded<T: Send + 'static>(
+        blocking: bool,
+        capacity: usize,
+    ) -> (
+        Box<dyn Receiver<T> + Sync + Send>,
+        Box<dyn Sender<T> + Sync + Send>,
+    ) {
+        let (tx, rx) = ff_buffer::build::<T>();
+        let backpressure = Some(Arc::new(Backpressure::new(capacity)));
+        if blocking {
+            (
+                Box::new(FFBlockingInputChannel {
+                    rx,
+                    backpressure: backpressure.clone(),
+                }),
+                Box::new(FFOutputChannel {
+                    tx: Mutex::new(tx),
+                    backpressure,
+                }),
             )
         } else {
             (
-                Box::new(FFInputChannel { rx }),
-                Box::new(FFOutputChannel { tx: Mutex::new(tx) }),
+                Box::new(FFInputChannel {
+                    rx,
+                    backpressure: backpressure.clone(),
+                }),
+                Box::new(FFOutputChannel {
+                    tx: Mutex::new(tx),
+                    backpressure,
+                }),
             )
         }
     }
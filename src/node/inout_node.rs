@@ -1,12 +1,14 @@
 use std::{
     collections::VecDeque,
+    hint,
     marker::PhantomData,
     sync::{
-        atomic::{AtomicUsize, Ordering},
+        atomic::{AtomicBool, AtomicUsize, Ordering},
         Arc, Barrier, Condvar, Mutex,
     },
 };
 
+use crossbeam_deque::{Injector, Steal, Stealer, Worker};
 use dyn_clone::DynClone;
 use log::{trace, warn};
 use std::collections::BTreeMap;
@@ -50,6 +52,15 @@ pub trait InOut<TIn, TOut>: DynClone {
     fn produce(&mut self) -> Option<TOut> {
         None
     }
+    /// Called once per replica of a node whose `is_producer()` is `false`,
+    /// when that replica receives `Task::Terminate`, giving a node that
+    /// accumulates state across `run()` calls (e.g. a key-grouped fold) a
+    /// chance to flush one last item summarizing everything it's seen so
+    /// far. Overload this to flush such state; the default is that there's
+    /// nothing buffered to flush. Producer nodes never have it called.
+    fn finalize(&mut self) -> Option<TOut> {
+        None
+    }
     /// This method return the number of replicas of the node.
     /// Overload this method allow to choose the number of replicas of the node.
     fn number_of_replicas(&self) -> usize {
@@ -60,12 +71,22 @@ pub trait InOut<TIn, TOut>: DynClone {
     fn is_ordered(&self) -> bool {
         false
     }
+    /// This method return a boolean that represent if the node's output is
+    /// broadcast to every replica of the next node, rather than routed to a
+    /// single one. Overload this method allow to choose if the node
+    /// broadcasts or not.
     fn broadcasting(&self) -> bool {
-        // to be implemented
         false
     }
+    /// This method returns a boolean that represents whether this node's
+    /// replicas emit all-to-all: every replica may send to any replica of
+    /// the next node, rather than sticking to the single partition/affinity
+    /// target the `counter` heuristic in `rts` otherwise computes. An
+    /// all-to-all node routes by its own replica id instead, and is
+    /// intended to be paired with an `A2AGather` (built via
+    /// `A2AGatherBuilder`) as the next node, which owns one channel per
+    /// upstream replica and merges them back into a single stream.
     fn a2a(&self) -> bool {
-        // to be implemented
         false
     }
     /// This method return a boolean that represent if the node is a producer or not.
@@ -73,6 +94,13 @@ pub trait InOut<TIn, TOut>: DynClone {
     fn is_producer(&self) -> bool {
         false
     }
+    /// This method returns the capacity of the channel feeding this node's
+    /// replicas. Overload this method to bound how many in-flight messages
+    /// a fast upstream stage may queue before `send` blocks it, applying
+    /// backpressure. `None` (the default) keeps the channel unbounded.
+    fn channel_capacity(&self) -> Option<usize> {
+        None
+    }
 }
 
 struct OrderedSplitter {
@@ -95,15 +123,124 @@ impl OrderedSplitter {
     }
 }
 
+/// Shared cooperative-cancellation handle for an `InOutNode`'s replicas,
+/// modeled on Tokio's `CancellationToken`. Setting it asks every replica's
+/// `rts` loop to stop processing new input, drop any buffered `produce()`
+/// output, and exit; `take_terminate_duty` picks exactly one replica to
+/// forward the single downstream `Task::Terminate`, since otherwise every
+/// replica that observes cancellation would each send its own.
+struct CancellationToken {
+    cancelled: AtomicBool,
+    terminate_sent: AtomicBool,
+}
+impl CancellationToken {
+    fn new() -> CancellationToken {
+        CancellationToken {
+            cancelled: AtomicBool::new(false),
+            terminate_sent: AtomicBool::new(false),
+        }
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    fn set_cancelled(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    /// Returns `true` for exactly the first caller across all replicas, so
+    /// only one of them forwards the single downstream `Task::Terminate`.
+    fn take_terminate_duty(&self) -> bool {
+        self.terminate_sent
+            .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+            .is_ok()
+    }
+}
+
+/// Default cap on how many out-of-order messages `InOutNode`'s reorder
+/// buffer (`storage`) will hold before `save_to_storage` applies
+/// backpressure. Keeps a slow/stuck `next_expected` ticket from letting an
+/// unbounded number of out-of-order arrivals pile up in memory.
+const DEFAULT_ORDER_BUFFER_CAPACITY: usize = 1024;
+
+/// Applies `handler.channel_capacity()`'s bound to the shared `injector`
+/// queue feeding unordered stages, the same way a bounded `Channel` applies
+/// it to an ordered stage's per-replica channel: `send`'s `!ordered` branch
+/// calls `acquire` before pushing onto `injector`, and `rts`'s unordered
+/// input path calls `release` once a replica actually dequeues (via a local
+/// pop or a steal, either of which consumes one item originally pushed
+/// through `injector`). Without this, `channel_capacity()` would be silently
+/// ignored for unordered stages, since `Injector` itself is unconditionally
+/// unbounded.
+struct InjectorBackpressure {
+    capacity: usize,
+    depth: AtomicUsize,
+    lock: Mutex<()>,
+    cvar: Condvar,
+}
+impl InjectorBackpressure {
+    fn new(capacity: usize) -> InjectorBackpressure {
+        InjectorBackpressure {
+            capacity,
+            depth: AtomicUsize::new(0),
+            lock: Mutex::new(()),
+            cvar: Condvar::new(),
+        }
+    }
+
+    /// Block until there is room for one more in-flight message, then
+    /// reserve it.
+    fn acquire(&self) {
+        let guard = self.lock.lock().unwrap();
+        let _guard = self
+            .cvar
+            .wait_while(guard, |_| self.depth.load(Ordering::Acquire) >= self.capacity)
+            .unwrap();
+        self.depth.fetch_add(1, Ordering::AcqRel);
+    }
+
+    /// Release a slot reserved by `acquire`, waking a parked sender.
+    fn release(&self) {
+        self.depth.fetch_sub(1, Ordering::AcqRel);
+        let _guard = self.lock.lock().unwrap();
+        self.cvar.notify_one();
+    }
+}
+
 pub struct InOutNode<TIn: Send, TOut: Send, TCollected, TNext: Node<TOut, TCollected>> {
     job_infos: Vec<JobInfo>,
     channels: Vec<OutputChannel<Message<TIn>>>,
     next_node: Arc<TNext>,
     ordered: bool,
     producer: bool,
+    broadcasting: bool,
     ordered_splitter: Arc<(Mutex<OrderedSplitter>, Condvar)>,
     storage: Mutex<BTreeMap<usize, Message<TIn>>>,
+    storage_not_full: Condvar,
+    order_buffer_capacity: usize,
     next_msg: AtomicUsize,
+    cancel_token: Arc<CancellationToken>,
+    /// Global work-stealing queue feeding this node's own replicas when
+    /// `!ordered`; see `rts`'s local-pop/steal/receive order. Unused (and
+    /// left empty) for ordered stages, which keep the static `channels[rec_id]`
+    /// routing instead.
+    injector: Arc<Injector<Message<TIn>>>,
+    /// Bounds `injector` at `handler.channel_capacity()`, the unordered
+    /// counterpart to `order_buffer_capacity`/bounded channels; `None` keeps
+    /// `injector` unbounded, matching `channel_capacity() == None`.
+    injector_backpressure: Option<Arc<InjectorBackpressure>>,
+    /// Duplicates an incoming `Task::NewTask`'s payload so `send` can fan it
+    /// out to every replica channel when `broadcasting` is set, without
+    /// requiring `TIn: Clone` on every `InOutNode`, broadcasting or not.
+    /// `None` unless this node was built via `new_broadcasting` (the only
+    /// constructor that requires `TIn: Clone`); `send`'s broadcasting branch
+    /// panics without one, so a handler whose `broadcasting()` returns
+    /// `true` must be built via `new_broadcasting`.
+    in_broadcaster: Option<Arc<dyn Fn(&TIn) -> TIn + Send + Sync>>,
+    /// Same idea for this node's own output, fanned out to every replica of
+    /// `next_node` in `rts` when `broadcasting` is set.
+    out_broadcaster: Option<Arc<dyn Fn(&TOut) -> TOut + Send + Sync>>,
     phantom: PhantomData<(TOut, TCollected)>,
 }
 
@@ -122,23 +259,51 @@ impl<
 
         let Message { op, order } = input;
         match &op {
-            Task::NewTask(_e) => {
+            Task::NewTask(e) => {
                 if self.channels.len() == 1
                     && self.ordered
                     && order != self.next_msg.load(Ordering::SeqCst)
                 {
                     self.save_to_storage(Message::new(op, rec_id), order);
                     self.send_pending();
-                } else {
-                    let res = self.channels[rec_id].send(Message::new(op, order));
-                    if res.is_err() {
-                        panic!("Error: Cannot send message!");
+                } else if self.broadcasting {
+                    let duplicate = self.in_broadcaster.as_ref().expect(
+                        "broadcasting node built without a TIn duplicator; \
+                         construct it via InOutNode::new_broadcasting",
+                    );
+                    for ch in &self.channels {
+                        let res = ch.send(Message::new(Task::NewTask(duplicate(e)), order));
+                        if res.is_err() {
+                            panic!("Error: Cannot send message!");
+                        }
                     }
 
                     if self.ordered {
                         let old_c = self.next_msg.load(Ordering::SeqCst);
                         self.next_msg.store(old_c + 1, Ordering::SeqCst);
                     }
+                } else if !self.ordered {
+                    // Unordered: hand the message to the shared work-stealing
+                    // queue instead of a fixed replica, so an idle replica can
+                    // steal it from a straggler instead of waiting its turn.
+                    // `injector_backpressure` applies the same
+                    // `channel_capacity()` bound the ordered branch gets from
+                    // `self.channels[rec_id]` being a bounded `Channel`,
+                    // since `Injector` itself is unconditionally unbounded.
+                    if let Some(bp) = &self.injector_backpressure {
+                        bp.acquire();
+                    }
+                    self.injector.push(Message::new(op, order));
+                } else {
+                    let res = self.channels[rec_id].send(Message::new(op, order));
+                    if res.is_err() {
+                        panic!("Error: Cannot send message!");
+                    }
+
+                    // An upstream broadcasting node sends this same `order`
+                    // to several of our replicas; `fetch_max` keeps this a
+                    // per-logical-input advance rather than per-copy.
+                    self.next_msg.fetch_max(order + 1, Ordering::SeqCst);
                 }
             }
             Task::Dropped => {
@@ -148,16 +313,30 @@ impl<
                 {
                     self.save_to_storage(Message::new(op, rec_id), order);
                     self.send_pending();
-                } else {
-                    let res = self.channels[rec_id].send(Message::new(op, order));
-                    if res.is_err() {
-                        panic!("Error: Cannot send message!");
+                } else if self.broadcasting {
+                    for ch in &self.channels {
+                        let res = ch.send(Message::new(Task::Dropped, order));
+                        if res.is_err() {
+                            panic!("Error: Cannot send message!");
+                        }
                     }
 
                     if self.ordered {
                         let old_c = self.next_msg.load(Ordering::SeqCst);
                         self.next_msg.store(old_c + 1, Ordering::SeqCst);
                     }
+                } else if !self.ordered {
+                    if let Some(bp) = &self.injector_backpressure {
+                        bp.acquire();
+                    }
+                    self.injector.push(Message::new(op, order));
+                } else {
+                    let res = self.channels[rec_id].send(Message::new(op, order));
+                    if res.is_err() {
+                        panic!("Error: Cannot send message!");
+                    }
+
+                    self.next_msg.fetch_max(order + 1, Ordering::SeqCst);
                 }
             }
             Task::Terminate => {
@@ -211,12 +390,112 @@ impl<
     /// If `blocking` is true the node will perform blocking operation on receive.
     /// If `pinning` is `true` the node will be pinned to the thread in position `id`.
     ///
+    /// When `handler.is_ordered()` is true and `next_node` has a single
+    /// replica, out-of-order arrivals from this node's replicas are held in
+    /// a bounded reorder buffer (see `with_order_buffer_capacity`) and
+    /// released to `next_node` in ticket order.
+    ///
+    /// If `handler.channel_capacity()` returns `Some(capacity)`, the
+    /// channels feeding this node's replicas are bounded at `capacity` (a
+    /// full channel blocks the sending replica until space frees up) and
+    /// the reorder buffer is capped at the same value; `None` keeps the
+    /// channels unbounded and caps the reorder buffer at
+    /// `DEFAULT_ORDER_BUFFER_CAPACITY`.
     pub fn new(
         id: usize,
         handler: Box<dyn InOut<TIn, TOut> + Send + Sync>,
         next_node: TNext,
         blocking: bool,
         orchestrator: Arc<Orchestrator>,
+    ) -> InOutNode<TIn, TOut, TCollected, TNext> {
+        let order_buffer_capacity = handler
+            .channel_capacity()
+            .unwrap_or(DEFAULT_ORDER_BUFFER_CAPACITY);
+        Self::build(
+            id,
+            handler,
+            next_node,
+            blocking,
+            orchestrator,
+            order_buffer_capacity,
+            None,
+            None,
+            None,
+        )
+    }
+
+    /// Like `new`, but lets the caller bound how many out-of-order messages
+    /// the reorder buffer may hold before `save_to_storage` blocks the
+    /// sending replica (backpressure). A smaller capacity trades throughput
+    /// under skewed replica speeds for a tighter memory bound.
+    pub fn with_order_buffer_capacity(
+        id: usize,
+        handler: Box<dyn InOut<TIn, TOut> + Send + Sync>,
+        next_node: TNext,
+        blocking: bool,
+        orchestrator: Arc<Orchestrator>,
+        order_buffer_capacity: usize,
+    ) -> InOutNode<TIn, TOut, TCollected, TNext> {
+        Self::build(
+            id,
+            handler,
+            next_node,
+            blocking,
+            orchestrator,
+            order_buffer_capacity,
+            None,
+            None,
+            None,
+        )
+    }
+
+    /// Like `new`, but forces order-preserving farm mode on or off instead
+    /// of deferring to `handler.is_ordered()`: every item entering the farm
+    /// keeps its input sequence number through to the fan-in node, which
+    /// only releases items to `next_node` once the contiguous next ticket
+    /// is available, buffering out-of-order arrivals (bounded the same way
+    /// as `with_order_buffer_capacity`).
+    pub fn with_ordering(
+        id: usize,
+        handler: Box<dyn InOut<TIn, TOut> + Send + Sync>,
+        next_node: TNext,
+        blocking: bool,
+        orchestrator: Arc<Orchestrator>,
+        ordered: bool,
+    ) -> InOutNode<TIn, TOut, TCollected, TNext> {
+        let order_buffer_capacity = handler
+            .channel_capacity()
+            .unwrap_or(DEFAULT_ORDER_BUFFER_CAPACITY);
+        Self::build(
+            id,
+            handler,
+            next_node,
+            blocking,
+            orchestrator,
+            order_buffer_capacity,
+            Some(ordered),
+            None,
+            None,
+        )
+    }
+
+    /// Shared constructor body for `new`/`with_order_buffer_capacity`/
+    /// `with_ordering`/`new_broadcasting`: `ordered_override` of `None`
+    /// defers to `handler.is_ordered()`, `Some(v)` forces it to `v`.
+    /// `in_broadcaster`/`out_broadcaster` are `Some` only when called from
+    /// `new_broadcasting`, the lone constructor that requires `TIn`/`TOut`
+    /// to be `Clone`; every other constructor passes `None` for both, so
+    /// the common, non-broadcasting case never needs `Clone` at all.
+    fn build(
+        id: usize,
+        handler: Box<dyn InOut<TIn, TOut> + Send + Sync>,
+        next_node: TNext,
+        blocking: bool,
+        orchestrator: Arc<Orchestrator>,
+        order_buffer_capacity: usize,
+        ordered_override: Option<bool>,
+        in_broadcaster: Option<Arc<dyn Fn(&TIn) -> TIn + Send + Sync>>,
+        out_broadcaster: Option<Arc<dyn Fn(&TOut) -> TOut + Send + Sync>>,
     ) -> InOutNode<TIn, TOut, TCollected, TNext> {
         let mut funcs = Vec::new();
         let mut channels = Vec::new();
@@ -224,8 +503,14 @@ impl<
         let replicas = handler.number_of_replicas();
 
         let splitter = Arc::new((Mutex::new(OrderedSplitter::new()), Condvar::new()));
-        let ordered = handler.is_ordered();
+        let ordered = ordered_override.unwrap_or_else(|| handler.is_ordered());
         let producer = handler.is_producer();
+        let broadcasting = handler.broadcasting();
+        let channel_capacity = handler.channel_capacity();
+        let cancel_token = Arc::new(CancellationToken::new());
+        let injector = Arc::new(Injector::new());
+        let injector_backpressure =
+            channel_capacity.map(|capacity| Arc::new(InjectorBackpressure::new(capacity)));
 
         let mut handler_copies = Vec::with_capacity(replicas);
         for _i in 0..replicas - 1 {
@@ -233,19 +518,51 @@ impl<
         }
         handler_copies.push(handler);
 
+        // One local deque per replica, plus a `Stealer` handle to each so an
+        // idle replica can steal from a busier peer (see `rts`). Only used
+        // when `!ordered`; ordered stages keep the static `channels[rec_id]`
+        // routing instead.
+        let mut local_workers: Vec<Worker<Message<TIn>>> =
+            (0..replicas).map(|_| Worker::new_fifo()).collect();
+        let stealers: Arc<Vec<Stealer<Message<TIn>>>> =
+            Arc::new(local_workers.iter().map(|w| w.stealer()).collect());
+
         let barrier = Arc::new(Barrier::new(replicas));
 
         for i in 0..replicas {
-            let (channel_in, channel_out) = Channel::channel(blocking);
+            let (channel_in, channel_out) = match channel_capacity {
+                Some(capacity) => Channel::bounded(blocking, capacity),
+                None => Channel::channel(blocking),
+            };
             channels.push(channel_out);
             let nn = Arc::clone(&next_node);
             let splitter_copy = Arc::clone(&splitter);
             let copy = handler_copies.pop().unwrap();
             let local_barrier = Arc::clone(&barrier);
+            let cancel_token_copy = Arc::clone(&cancel_token);
+            let injector_copy = Arc::clone(&injector);
+            let injector_backpressure_copy = injector_backpressure.clone();
+            let stealers_copy = Arc::clone(&stealers);
+            let local_worker = local_workers.pop().unwrap();
+            let out_broadcaster_copy = out_broadcaster.clone();
 
             let func = move || {
                 local_barrier.wait();
-                Self::rts(i + id, copy, channel_in, &nn, replicas, &splitter_copy);
+                Self::rts(
+                    i + id,
+                    copy,
+                    channel_in,
+                    &nn,
+                    replicas,
+                    ordered,
+                    &splitter_copy,
+                    &cancel_token_copy,
+                    &injector_copy,
+                    injector_backpressure_copy,
+                    local_worker,
+                    &stealers_copy,
+                    out_broadcaster_copy,
+                );
             };
 
             funcs.push(func);
@@ -257,51 +574,177 @@ impl<
             next_node,
             ordered,
             producer,
+            broadcasting,
             ordered_splitter: splitter,
             storage: Mutex::new(BTreeMap::new()),
+            storage_not_full: Condvar::new(),
+            order_buffer_capacity,
+            injector,
+            injector_backpressure,
+            in_broadcaster,
+            out_broadcaster,
             next_msg: AtomicUsize::new(0),
+            cancel_token,
             phantom: PhantomData,
         }
     }
 
+    /// Try to find a message for an idle replica without blocking: first a
+    /// batch stolen from the shared `injector`, then a single item stolen
+    /// from a busier peer's local deque. Only called for unordered stages —
+    /// `InOutNode::send`'s `!ordered` branch is the only place messages are
+    /// ever pushed onto `injector` or a replica's local deque in the first
+    /// place.
+    fn steal_task(
+        injector: &Injector<Message<TIn>>,
+        local_worker: &Worker<Message<TIn>>,
+        stealers: &[Stealer<Message<TIn>>],
+    ) -> Option<Message<TIn>> {
+        loop {
+            match injector.steal_batch_and_pop(local_worker) {
+                Steal::Success(msg) => return Some(msg),
+                Steal::Retry => continue,
+                Steal::Empty => break,
+            }
+        }
+        for stealer in stealers {
+            loop {
+                match stealer.steal() {
+                    Steal::Success(msg) => return Some(msg),
+                    Steal::Retry => continue,
+                    Steal::Empty => break,
+                }
+            }
+        }
+        None
+    }
+
     fn rts(
         id: usize,
         mut node: Box<dyn InOut<TIn, TOut>>,
         channel_in: InputChannel<Message<TIn>>,
         next_node: &TNext,
         n_replicas: usize,
+        ordered: bool,
         ordered_splitter_handler: &(Mutex<OrderedSplitter>, Condvar),
+        cancel_token: &CancellationToken,
+        injector: &Injector<Message<TIn>>,
+        injector_backpressure: Option<Arc<InjectorBackpressure>>,
+        local_worker: Worker<Message<TIn>>,
+        stealers: &[Stealer<Message<TIn>>],
+        out_broadcaster: Option<Arc<dyn Fn(&TOut) -> TOut + Send + Sync>>,
     ) {
         // If next node have more replicas, i specify the first next node where i send my msg
         let mut counter = 0;
-        if (next_node.get_num_of_replicas() > n_replicas) && n_replicas != 1 {
+        let a2a = node.a2a();
+        if a2a {
+            // All-to-all: `next_node` is expected to be an `A2AGather`
+            // owning one dedicated channel per upstream replica, so this
+            // replica always routes by its own id rather than the
+            // partition/round-robin heuristic below, which only
+            // approximates fan-in.
+            counter = id;
+        } else if (next_node.get_num_of_replicas() > n_replicas) && n_replicas != 1 {
             counter = id * (next_node.get_num_of_replicas() / n_replicas);
         } else if next_node.get_num_of_replicas() <= n_replicas {
-            // Standard case, not a2a
             counter = id;
         }
         trace!("Created a new Node! Id: {}", id);
-        loop {
+
+        // Forwards the single downstream `Task::Terminate` (only for the
+        // replica that wins `take_terminate_duty`) and wakes any peer
+        // parked in the ordered producer handoff below.
+        let abort = |counter: usize| {
+            // Drain whatever this replica's channel is still holding before
+            // exiting: once this loop stops calling `channel_in.receive()`,
+            // nobody will ever release the backpressure credits those
+            // queued messages hold, and `cancel()`'s own `Task::Terminate`
+            // push could then block forever on a channel that never frees
+            // up. Discarding them here (rather than processing them) is
+            // fine — cancellation means downstream no longer cares.
+            while !channel_in.is_empty() {
+                let _ = channel_in.receive();
+            }
+            if cancel_token.take_terminate_duty() {
+                let err = next_node.send(Message::new(Task::Terminate, 0), counter);
+                if err.is_err() {
+                    panic!("Error: {}", err.unwrap_err())
+                }
+            }
+            let (_lock, cvar) = ordered_splitter_handler;
+            cvar.notify_all();
+        };
+
+        'replica: loop {
+            if cancel_token.is_cancelled() {
+                abort(counter);
+                break 'replica;
+            }
             // If next node have more replicas, when counter > next_replicas i reset the counter
-            if (next_node.get_num_of_replicas() > n_replicas)
+            if !a2a
+                && (next_node.get_num_of_replicas() > n_replicas)
                 && counter >= next_node.get_num_of_replicas()
             {
                 counter = 0;
             }
 
-            let input = channel_in.receive();
+            // Ordered stages keep the static channel-per-replica routing
+            // (see `InOutNode::send`); unordered ones try their own deque,
+            // then stealing from a busier peer, before blocking on the
+            // channel — which by now only ever carries a `Task::Terminate`.
+            let input = if ordered {
+                channel_in.receive()
+            } else {
+                match local_worker.pop() {
+                    Some(msg) => {
+                        if let Some(bp) = &injector_backpressure {
+                            bp.release();
+                        }
+                        Ok(Some(msg))
+                    }
+                    None => match Self::steal_task(injector, &local_worker, stealers) {
+                        Some(msg) => {
+                            if let Some(bp) = &injector_backpressure {
+                                bp.release();
+                            }
+                            Ok(Some(msg))
+                        }
+                        None => channel_in.receive(),
+                    },
+                }
+            };
 
             match input {
                 Ok(Some(Message { op, order })) => match op {
                     Task::NewTask(arg) => {
+                        if cancel_token.is_cancelled() {
+                            abort(counter);
+                            break 'replica;
+                        }
                         let output = node.run(arg);
                         if !node.is_producer() {
                             match output {
                                 Some(msg) => {
-                                    let err = next_node
-                                        .send(Message::new(Task::NewTask(msg), order), counter);
-                                    if err.is_err() {
-                                        panic!("Error: {}", err.unwrap_err())
+                                    if node.broadcasting() {
+                                        let duplicate = out_broadcaster.as_ref().expect(
+                                            "broadcasting node built without a TOut duplicator; \
+                                             construct it via InOutNode::new_broadcasting",
+                                        );
+                                        for rec in 0..next_node.get_num_of_replicas() {
+                                            let err = next_node.send(
+                                                Message::new(Task::NewTask(duplicate(&msg)), order),
+                                                rec,
+                                            );
+                                            if err.is_err() {
+                                                panic!("Error: {}", err.unwrap_err())
+                                            }
+                                        }
+                                    } else {
+                                        let err = next_node
+                                            .send(Message::new(Task::NewTask(msg), order), counter);
+                                        if err.is_err() {
+                                            panic!("Error: {}", err.unwrap_err())
+                                        }
                                     }
                                 }
                                 None => {
@@ -314,7 +757,12 @@ impl<
                             }
                         } else {
                             let mut tmp = VecDeque::new();
+                            let mut cancelled_mid_produce = false;
                             loop {
+                                if cancel_token.is_cancelled() {
+                                    cancelled_mid_produce = true;
+                                    break;
+                                }
                                 let splitter_out = node.produce();
                                 match splitter_out {
                                     Some(msg) => {
@@ -323,11 +771,28 @@ impl<
                                     None => break,
                                 }
                             }
+                            if cancelled_mid_produce {
+                                // Drop whatever we've buffered so far instead
+                                // of forwarding it.
+                                tmp.clear();
+                                abort(counter);
+                                break 'replica;
+                            }
 
-                            if node.is_ordered() {
+                            if ordered {
                                 let (lock, cvar) = ordered_splitter_handler;
                                 let mut ordered_splitter = lock.lock().unwrap();
+                                let mut cancelled_in_handoff = false;
                                 loop {
+                                    if cancel_token.is_cancelled() {
+                                        // Still holding the lock: wake any
+                                        // peer parked below on `cvar.wait`
+                                        // before giving it up, or they'd
+                                        // never be notified again.
+                                        cancelled_in_handoff = true;
+                                        cvar.notify_all();
+                                        break;
+                                    }
                                     let (latest, end) = ordered_splitter.get();
                                     if latest == order {
                                         let mut count_splitter = end;
@@ -356,6 +821,17 @@ impl<
                                         }
                                     }
                                 }
+                                // Drop the splitter lock before calling
+                                // `abort`, which may block on
+                                // `next_node.send`: holding the lock across
+                                // that send would stall any peer parked on
+                                // `cvar.wait(ordered_splitter)` above until
+                                // the send completes.
+                                drop(ordered_splitter);
+                                if cancelled_in_handoff {
+                                    abort(counter);
+                                    break 'replica;
+                                }
                             } else {
                                 while !tmp.is_empty() {
                                     let err = next_node.send(
@@ -379,6 +855,15 @@ impl<
                         }
                     }
                     Task::Terminate => {
+                        if !node.is_producer() {
+                            if let Some(msg) = node.finalize() {
+                                let err = next_node
+                                    .send(Message::new(Task::NewTask(msg), order), counter);
+                                if err.is_err() {
+                                    panic!("Error: {}", err.unwrap_err())
+                                }
+                            }
+                        }
                         break;
                     }
                 },
@@ -387,7 +872,7 @@ impl<
                     warn!("Error: {}", e);
                 }
             }
-            if next_node.get_num_of_replicas() > n_replicas {
+            if !a2a && next_node.get_num_of_replicas() > n_replicas {
                 counter += 1;
             }
         }
@@ -407,17 +892,52 @@ impl<
             let ordered_splitter = lock.lock().unwrap();
             (_, c) = ordered_splitter.get();
         }
-        let err = self.next_node.send(Message::new(Task::Terminate, c), 0);
-        if err.is_err() {
-            panic!("Error: Cannot send message!");
+        // `cancel()` may already have had one replica forward its own
+        // `Task::Terminate` via `take_terminate_duty` (see `abort` in
+        // `rts`); only send ours if that hasn't happened; otherwise this
+        // natural end-of-stream path would send a second `Task::Terminate`
+        // into a channel the first one may have already torn down.
+        if self.cancel_token.take_terminate_duty() {
+            let err = self.next_node.send(Message::new(Task::Terminate, c), 0);
+            if err.is_err() {
+                panic!("Error: Cannot send message!");
+            }
         }
     }
 
+    /// Cooperatively cancel this node before natural end-of-stream. Each
+    /// replica's `rts` loop stops calling `node.run`/`produce()` as soon as
+    /// it next observes the cancellation, drops any buffered output, and
+    /// exits; exactly one replica forwards a single `Task::Terminate`
+    /// downstream. A replica currently parked in a blocking
+    /// `channel_in.receive()` is woken by pushing a `Task::Terminate` into
+    /// its channel, which `rts` already treats as a plain, unconditional
+    /// exit.
+    ///
+    /// A replica that instead observes the cancellation at the *top* of its
+    /// loop (rather than while parked in `receive()`) drains its channel on
+    /// the way out (see `rts`'s `abort` closure) before this call's own
+    /// `ch.send` below, so that send can't block forever on a channel whose
+    /// only remaining reader just exited without consuming it.
+    pub fn cancel(&self) {
+        self.cancel_token.set_cancelled();
+        for ch in &self.channels {
+            let _ = ch.send(Message::new(Task::Terminate, 0));
+        }
+    }
+
+    /// Buffer an out-of-order message, applying backpressure (blocking the
+    /// calling replica) while the reorder buffer already holds
+    /// `order_buffer_capacity` tickets ahead of `next_msg`.
     fn save_to_storage(&self, msg: Message<TIn>, order: usize) {
         let mtx = self.storage.lock();
 
         match mtx {
             Ok(mut queue) => {
+                queue = self
+                    .storage_not_full
+                    .wait_while(queue, |queue| queue.len() >= self.order_buffer_capacity)
+                    .unwrap();
                 queue.insert(order, msg);
             }
             Err(_) => panic!("Error: Cannot lock the storage!"),
@@ -458,5 +978,261 @@ impl<
             }
             Err(_) => panic!("Error: Cannot lock the storage!"),
         }
+        // Releasing buffered slots may let a replica blocked in
+        // `save_to_storage` make progress.
+        self.storage_not_full.notify_all();
+    }
+}
+
+impl<
+        TIn: Send + Clone + 'static,
+        TOut: Send + Clone + 'static,
+        TCollected,
+        TNext: Node<TOut, TCollected> + Sync + Send + 'static,
+    > InOutNode<TIn, TOut, TCollected, TNext>
+{
+    /// Like `new`, but for a `handler` whose `broadcasting()` returns
+    /// `true`: fanning a single incoming item out to every replica's
+    /// channel, or fanning a replica's output out to every replica of
+    /// `next_node`, needs to duplicate it, so this is the only constructor
+    /// that requires `TIn`/`TOut: Clone` — every other constructor can't
+    /// reach a clone at all, so it doesn't need the bound.
+    pub fn new_broadcasting(
+        id: usize,
+        handler: Box<dyn InOut<TIn, TOut> + Send + Sync>,
+        next_node: TNext,
+        blocking: bool,
+        orchestrator: Arc<Orchestrator>,
+    ) -> InOutNode<TIn, TOut, TCollected, TNext> {
+        let order_buffer_capacity = handler
+            .channel_capacity()
+            .unwrap_or(DEFAULT_ORDER_BUFFER_CAPACITY);
+        Self::build(
+            id,
+            handler,
+            next_node,
+            blocking,
+            orchestrator,
+            order_buffer_capacity,
+            None,
+            Some(Arc::new(|v: &TIn| v.clone())),
+            Some(Arc::new(|v: &TOut| v.clone())),
+        )
+    }
+}
+
+/// Many-to-one gather stage completing an all-to-all (`a2a`) topology: an
+/// upstream stage whose handler's `a2a()` returns `true` sends every
+/// replica's output here, one dedicated channel per upstream replica
+/// (`send`'s `rec_id` picks which, matching the replica's own id — see
+/// `InOutNode::rts`'s `a2a` handling). A single collector thread merges
+/// whichever channel has a message ready into one stream for `next_node`,
+/// re-sequencing by `order` when built `ordered`. Build one with
+/// `A2AGatherBuilder`.
+pub struct A2AGather<TIn: Send, TCollected, TNext: Node<TIn, TCollected>> {
+    channels: Vec<OutputChannel<Message<TIn>>>,
+    job_info: JobInfo,
+    next_node: Arc<TNext>,
+    phantom: PhantomData<TCollected>,
+}
+
+impl<TIn: Send + 'static, TCollected, TNext: Node<TIn, TCollected> + Send + Sync + 'static>
+    A2AGather<TIn, TCollected, TNext>
+{
+    fn new(
+        num_inputs: usize,
+        next_node: TNext,
+        blocking: bool,
+        ordered: bool,
+        channel_capacity: Option<usize>,
+        orchestrator: Arc<Orchestrator>,
+    ) -> A2AGather<TIn, TCollected, TNext> {
+        let next_node = Arc::new(next_node);
+        let mut channels = Vec::with_capacity(num_inputs);
+        let mut channel_ins = Vec::with_capacity(num_inputs);
+        for _ in 0..num_inputs {
+            let (channel_in, channel_out) = match channel_capacity {
+                Some(capacity) => Channel::bounded(blocking, capacity),
+                None => Channel::channel(blocking),
+            };
+            channels.push(channel_out);
+            channel_ins.push(channel_in);
+        }
+
+        let nn = Arc::clone(&next_node);
+        let func = move || Self::collect(channel_ins, &nn, ordered);
+        let mut funcs = Vec::new();
+        funcs.push(func);
+        let job_info = orchestrator.push_multiple(funcs).pop().unwrap();
+
+        A2AGather {
+            channels,
+            job_info,
+            next_node,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Runs on the single collector thread. The `Receiver` trait exposes
+    /// only a plain (blocking or non-blocking) `receive`, not a wait-on-any
+    /// primitive, so a true OS-level `select` over heterogeneous channel
+    /// backends isn't available here; fairness is instead approximated by
+    /// rotating which channel the scan starts from each sweep, rather than
+    /// always polling channel 0 first.
+    fn collect(channel_ins: Vec<InputChannel<Message<TIn>>>, next_node: &TNext, ordered: bool) {
+        let n = channel_ins.len();
+        let mut scan_start = 0;
+        let mut remaining = n;
+        let mut storage: BTreeMap<usize, Message<TIn>> = BTreeMap::new();
+        let mut next_msg = 0;
+        let mut final_order = 0;
+
+        'collect: loop {
+            let mut received = None;
+            for offset in 0..n {
+                let idx = (scan_start + offset) % n;
+                match channel_ins[idx].receive() {
+                    Ok(Some(msg)) => {
+                        received = Some(msg);
+                        scan_start = (idx + 1) % n;
+                        break;
+                    }
+                    Ok(None) => continue,
+                    Err(e) => {
+                        warn!("Error: {}", e);
+                    }
+                }
+            }
+
+            let Message { op, order } = match received {
+                Some(msg) => msg,
+                None => {
+                    if remaining == 0 {
+                        break 'collect;
+                    }
+                    hint::spin_loop();
+                    continue;
+                }
+            };
+
+            match op {
+                Task::Terminate => {
+                    remaining -= 1;
+                    final_order = final_order.max(order);
+                    if remaining == 0 {
+                        break 'collect;
+                    }
+                }
+                _ if ordered && order != next_msg => {
+                    storage.insert(order, Message::new(op, order));
+                }
+                _ => {
+                    let err = next_node.send(Message::new(op, order), 0);
+                    if err.is_err() {
+                        panic!("Error: {}", err.unwrap_err())
+                    }
+                    if ordered {
+                        next_msg += 1;
+                        while let Some(Message { op, order }) = storage.remove(&next_msg) {
+                            let err = next_node.send(Message::new(op, order), 0);
+                            if err.is_err() {
+                                panic!("Error: {}", err.unwrap_err())
+                            }
+                            next_msg += 1;
+                        }
+                    }
+                }
+            }
+        }
+
+        let err = next_node.send(Message::new(Task::Terminate, final_order), 0);
+        if err.is_err() {
+            panic!("Error: {}", err.unwrap_err())
+        }
+    }
+}
+
+impl<TIn: Send + 'static, TCollected, TNext: Node<TIn, TCollected> + Send + Sync + 'static>
+    Node<TIn, TCollected> for A2AGather<TIn, TCollected, TNext>
+{
+    fn send(&self, input: Message<TIn>, rec_id: usize) -> Result<(), ChannelError> {
+        let mut rec_id = rec_id;
+        if rec_id >= self.channels.len() {
+            rec_id %= self.channels.len();
+        }
+        self.channels[rec_id].send(input)
+    }
+
+    fn collect(self) -> Option<TCollected> {
+        self.job_info.wait();
+        match Arc::try_unwrap(self.next_node) {
+            Ok(nn) => nn.collect(),
+            Err(_) => panic!("Error: Cannot collect results inout."),
+        }
+    }
+
+    fn get_num_of_replicas(&self) -> usize {
+        self.channels.len()
+    }
+}
+
+/// Builds an [`A2AGather`]: the single collector ("1 gather") side of an
+/// N-producer -> M-consumer -> 1-gather all-to-all topology, wiring it
+/// explicitly instead of relying on `rts`'s implicit counter heuristic.
+pub struct A2AGatherBuilder {
+    num_inputs: usize,
+    blocking: bool,
+    ordered: bool,
+    channel_capacity: Option<usize>,
+}
+
+impl A2AGatherBuilder {
+    /// `num_inputs` is the number of upstream replicas that will call
+    /// `send` on the built gather, one dedicated channel each.
+    pub fn new(num_inputs: usize) -> A2AGatherBuilder {
+        A2AGatherBuilder {
+            num_inputs,
+            blocking: false,
+            ordered: false,
+            channel_capacity: None,
+        }
+    }
+
+    /// If `true` the gather's channels perform blocking receives.
+    pub fn blocking(mut self, blocking: bool) -> A2AGatherBuilder {
+        self.blocking = blocking;
+        self
+    }
+
+    /// If `true`, re-sequence merged messages by `order` (buffering
+    /// out-of-order arrivals) instead of forwarding them as they arrive.
+    pub fn ordered(mut self, ordered: bool) -> A2AGatherBuilder {
+        self.ordered = ordered;
+        self
+    }
+
+    /// Bound each upstream channel's capacity; see `InOut::channel_capacity`.
+    pub fn channel_capacity(mut self, capacity: usize) -> A2AGatherBuilder {
+        self.channel_capacity = Some(capacity);
+        self
+    }
+
+    pub fn build<TIn, TCollected, TNext>(
+        self,
+        next_node: TNext,
+        orchestrator: Arc<Orchestrator>,
+    ) -> A2AGather<TIn, TCollected, TNext>
+    where
+        TIn: Send + 'static,
+        TNext: Node<TIn, TCollected> + Send + Sync + 'static,
+    {
+        A2AGather::new(
+            self.num_inputs,
+            next_node,
+            self.blocking,
+            self.ordered,
+            self.channel_capacity,
+            orchestrator,
+        )
     }
 }
@@ -55,6 +55,47 @@ macro_rules! pipeline {
     };
 }
 
+/// Like `pipeline_propagate!`, but forces every stage's farm to preserve
+/// input order via `InOutNode::with_ordering(..., true)` instead of
+/// deferring to each stage's `is_ordered()`. Use via `ordered_pipeline!`
+/// when a stage that isn't ordered by default (e.g. one built from a plain
+/// closure) still needs its replicas' outputs delivered in arrival order.
+#[macro_export]
+macro_rules! ordered_pipeline_propagate {
+    ($s1:expr) => {
+        {
+            let mut block = InNode::new(0, $s1, false).unwrap();
+            block
+        }
+    };
+
+    ($s1:expr $(, $tail:expr)*) => {
+        {
+            let registry = crate::core::orchestrator::get_global_orchestrator();
+            let mut block = InOutNode::with_ordering(0, $s1,
+                ordered_pipeline_propagate!($($tail),*),
+                false, registry, true);
+            block
+        }
+    };
+}
+
+/// Like `pipeline!`, but builds every stage with order-preservation forced
+/// on; see `ordered_pipeline_propagate!`.
+#[macro_export]
+macro_rules! ordered_pipeline {
+    ($s1:expr $(, $tail:expr)*) => {
+        {
+            let mut block = OutNode::new(0, $s1,
+                ordered_pipeline_propagate!($($tail),*)).unwrap();
+
+            let mut pipeline = Pipeline::new(block);
+            pipeline.start();
+            pipeline
+        }
+    };
+}
+
 pub struct Parallel<TOut: Send, TCollected, TNext: Node<TOut, TCollected>> {
     first_block: OutMoNode<TOut, TCollected, TNext>,
 }
@@ -119,3 +160,61 @@ macro_rules! parallel {
         }
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::{in_node::In, inout_node::InOut, out_node::Out};
+    use serial_test::serial;
+
+    struct Source {
+        streamlen: usize,
+    }
+    impl Out<usize> for Source {
+        fn run(&mut self) -> Option<usize> {
+            if self.streamlen > 0 {
+                self.streamlen -= 1;
+                Some(self.streamlen)
+            } else {
+                None
+            }
+        }
+    }
+
+    #[derive(Clone)]
+    struct Worker {}
+    impl InOut<usize, usize> for Worker {
+        fn run(&mut self, input: usize) -> Option<usize> {
+            Some(input)
+        }
+    }
+
+    struct Sink {
+        counter: usize,
+    }
+    impl In<usize, usize> for Sink {
+        fn run(&mut self, _input: usize) {
+            self.counter += 1;
+        }
+        fn finalize(self) -> Option<usize> {
+            Some(self.counter)
+        }
+    }
+
+    // Regression test for ordered_pipeline_propagate!/ordered_pipeline!:
+    // previously the recursive arm called `InOutNode::with_ordering` with
+    // the wrong argument count and `.unwrap()`-ed a value that isn't a
+    // `Result`, so this never actually compiled.
+    #[test]
+    #[serial]
+    fn test_ordered_pipeline_expands_and_runs() {
+        let mut p = ordered_pipeline![
+            Source { streamlen: 10 },
+            Worker {},
+            Sink { counter: 0 }
+        ];
+        p.start();
+        let res = p.collect();
+        assert_eq!(res.unwrap(), 10);
+    }
+}
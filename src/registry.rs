@@ -1,18 +1,106 @@
-use std::{sync::{Arc, Mutex, Barrier, RwLock}, thread};
+use std::{any::Any, cell::Cell, marker::PhantomData, panic::{self, AssertUnwindSafe}, sync::{Arc, Mutex, Barrier, RwLock, Condvar, atomic::{AtomicUsize, Ordering}}, thread};
 
 use crossbeam_deque::{Stealer, Injector, Worker, Steal};
 use log::{trace, error};
 
 type Func<'a> = Box<dyn FnOnce() + Send + 'a>;
 
+/// Number of failed search rounds a worker will busy-spin through before
+/// it starts trying to park on the `Sleep` subsystem.
+const ROUNDS_UNTIL_SLEEPY: usize = 32;
+
 pub(super) enum Job {
     NewJob(Func<'static>),
     Terminate,
 }
+
+/// Tracks how many workers are drowsy or fully parked, so that producers
+/// only pay for a `notify_all` when there is actually someone to wake up.
+struct SleepState {
+    sleepy: usize,
+    sleeping: usize,
+}
+
+/// Sleep/wake subsystem used by idle workers instead of busy-spinning.
+///
+/// Workers follow a two-phase "sleepy -> sleeping" protocol to avoid lost
+/// wakeups: a worker first marks itself sleepy, takes a final look at every
+/// queue, and only parks on the `Condvar` if nothing showed up *and* the
+/// `jobs_event` counter below didn't move in the meantime. Producers bump
+/// `jobs_event` after every push and only call `notify_all` when
+/// `SleepState` reports a sleepy or sleeping worker. Every push still
+/// takes the `state` lock to read those counts, even when no worker is idle.
+pub(super) struct Sleep {
+    state: Mutex<SleepState>,
+    condvar: Condvar,
+    jobs_event: AtomicUsize,
+}
+
+impl Sleep {
+    fn new() -> Sleep {
+        Sleep {
+            state: Mutex::new(SleepState { sleepy: 0, sleeping: 0 }),
+            condvar: Condvar::new(),
+            jobs_event: AtomicUsize::new(0),
+        }
+    }
+
+    /// Snapshot of the jobs-event counter, to be compared later to detect
+    /// whether any work was pushed since it was taken.
+    fn event_counter(&self) -> usize {
+        self.jobs_event.load(Ordering::SeqCst)
+    }
+
+    /// Called by producers (`Registry::execute`, `WorkerThread::push`, ...)
+    /// right after a job has been made visible in the `Injector` or in a
+    /// worker's local deque.
+    fn notify_work(&self) {
+        self.jobs_event.fetch_add(1, Ordering::SeqCst);
+        let state = self.state.lock().unwrap();
+        if state.sleepy > 0 || state.sleeping > 0 {
+            self.condvar.notify_all();
+        }
+    }
+
+    /// Park the calling worker until new work is pushed, unless `last_event`
+    /// no longer matches the current jobs-event counter (meaning work
+    /// arrived between the last failed search and this call).
+    fn sleep(&self, last_event: usize, still_empty: impl FnOnce() -> bool) {
+        let mut state = self.state.lock().unwrap();
+        state.sleepy += 1;
+
+        // Final re-check under the lock: if a job landed after our last
+        // search but before we marked ourselves sleepy, don't sleep through it.
+        if self.jobs_event.load(Ordering::SeqCst) != last_event || !still_empty() {
+            state.sleepy -= 1;
+            return;
+        }
+
+        state.sleepy -= 1;
+        state.sleeping += 1;
+        let mut state = self
+            .condvar
+            .wait_while(state, |_| {
+                self.jobs_event.load(Ordering::SeqCst) == last_event
+            })
+            .unwrap();
+        state.sleeping -= 1;
+    }
+
+    /// Wake every sleepy or parked worker, used to propagate `Terminate`
+    /// so the drain loop in `Drop for Registry` can still join everyone.
+    fn wake_all(&self) {
+        self.jobs_event.fetch_add(1, Ordering::SeqCst);
+        let _state = self.state.lock().unwrap();
+        self.condvar.notify_all();
+    }
+}
+
 pub(super) struct Registry {
     workers: Vec<Arc<WorkerThread>>,
     threads: Vec<Thread>,
     global: Arc<Injector<Job>>,
+    sleep: Arc<Sleep>,
 }
 impl Registry {
     /// Create a new threadpool with `nthreads` threads.
@@ -22,11 +110,12 @@ impl Registry {
         let mut workers = Vec::new();
         let mut threads = Vec::new();
         let global = Arc::new(Injector::new());
+        let sleep = Arc::new(Sleep::new());
 
         let barrier = Arc::new(Barrier::new(nthreads));
 
         for i in 0..nthreads {
-            let worker = WorkerThread::new(i, Arc::clone(&global));
+            let worker = WorkerThread::new(i, Arc::clone(&global), Arc::clone(&sleep));
             workers.push(Arc::new(worker));
         }
 
@@ -41,7 +130,7 @@ impl Registry {
             let local_barrier = Arc::clone(&barrier);
 
             let thread = Thread::new(worker_copy.id,  move ||
-               { 
+               {
                 local_barrier.wait();
                 worker_copy.run();
                }
@@ -49,11 +138,12 @@ impl Registry {
 
             threads.push(thread);
         }
-        
+
         Registry {
             workers,
             threads,
             global,
+            sleep,
         }
     }
 
@@ -64,13 +154,159 @@ impl Registry {
     {
         let job = Job::NewJob(Box::new(f));
         self.global.push(job);
+        self.sleep.notify_work();
+    }
+
+    /// Run `f` exactly once on each worker thread, passing the worker's
+    /// index, and block until every worker has completed it.
+    ///
+    /// Unlike `execute`, the job is never dispatched through the shared
+    /// `Injector` and can't be stolen: it is handed directly to each
+    /// `WorkerThread`, which runs it ahead of anything in its local deque
+    /// the next time it looks for work. This makes it suitable for
+    /// per-thread initialization (thread-local buffers, warming caches,
+    /// pinned allocations) and for collective operations that must touch
+    /// every worker rather than a random subset of them.
+    pub fn broadcast<F>(&self, f: F)
+    where
+        F: Fn(usize) + Send + Sync + 'static,
+    {
+        self.broadcast_with_result(move |id| f(id));
+    }
+
+    /// Like `broadcast`, but collects the return value of `f` from every
+    /// worker into a `Vec` ordered by worker index.
+    pub fn broadcast_with_result<F, R>(&self, f: F) -> Vec<R>
+    where
+        F: Fn(usize) -> R + Send + Sync + 'static,
+        R: Send + 'static,
+    {
+        let nthreads = self.workers.len();
+        let f = Arc::new(f);
+        let countdown = Arc::new(AtomicUsize::new(nthreads));
+        let done = Arc::new((Mutex::new(()), Condvar::new()));
+        let results: Vec<Mutex<Option<R>>> = (0..nthreads).map(|_| Mutex::new(None)).collect();
+        let results = Arc::new(results);
+
+        for worker in &self.workers {
+            let f = Arc::clone(&f);
+            let countdown = Arc::clone(&countdown);
+            let done = Arc::clone(&done);
+            let results = Arc::clone(&results);
+            let id = worker.id;
+
+            worker.set_broadcast_job(Box::new(move || {
+                *results[id].lock().unwrap() = Some(f(id));
+                if countdown.fetch_sub(1, Ordering::AcqRel) == 1 {
+                    let (lock, cvar) = &*done;
+                    let _guard = lock.lock().unwrap();
+                    cvar.notify_all();
+                }
+            }));
+        }
+
+        // Broadcast jobs sit in a dedicated per-worker slot rather than the
+        // Injector, so simply wake every parked worker to have them notice it.
+        self.sleep.wake_all();
+
+        let (lock, cvar) = &*done;
+        let guard = lock.lock().unwrap();
+        let _guard = cvar
+            .wait_while(guard, |_| countdown.load(Ordering::Acquire) != 0)
+            .unwrap();
+
+        Arc::try_unwrap(results)
+            .unwrap_or_else(|_| unreachable!("no worker keeps a reference after completion"))
+            .into_iter()
+            .map(|cell| cell.into_inner().unwrap().expect("broadcast job did not run"))
+            .collect()
+    }
+
+    /// Open a scope in which jobs may borrow data that lives on the
+    /// caller's stack instead of requiring `F: 'static`.
+    ///
+    /// `f` receives a `Scope` whose `spawn` accepts closures bound only by
+    /// the scope's lifetime; `scope` blocks after `f` returns until every
+    /// job spawned into the scope has completed, so no borrowed data can be
+    /// dropped while a job might still be using it. This removes the
+    /// `Arc`/clone boilerplate `execute`'s `'static` bound otherwise forces
+    /// onto data-parallel call sites.
+    pub fn scope<'scope, F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&Scope<'_, 'scope>) -> R,
+    {
+        let scope = Scope {
+            registry: self,
+            outstanding: Arc::new(AtomicUsize::new(0)),
+            done: Arc::new((Mutex::new(()), Condvar::new())),
+            panic: Arc::new(Mutex::new(None)),
+            _marker: PhantomData,
+        };
+        let result = f(&scope);
+        scope.wait();
+        if let Some(payload) = scope.panic.lock().unwrap().take() {
+            panic::resume_unwind(payload);
+        }
+        result
+    }
+
+}
+
+/// A scope created by `Registry::scope`. See its documentation for details.
+pub(super) struct Scope<'registry, 'scope> {
+    registry: &'registry Registry,
+    outstanding: Arc<AtomicUsize>,
+    done: Arc<(Mutex<()>, Condvar)>,
+    panic: Arc<Mutex<Option<Box<dyn Any + Send + 'static>>>>,
+    _marker: PhantomData<Cell<&'scope mut ()>>,
+}
+
+impl<'registry, 'scope> Scope<'registry, 'scope> {
+    /// Spawn a job that may borrow data from the enclosing scope. The job
+    /// is guaranteed to finish before `Registry::scope` returns.
+    pub fn spawn<F>(&self, f: F)
+    where
+        F: FnOnce() + Send + 'scope,
+    {
+        self.outstanding.fetch_add(1, Ordering::SeqCst);
+        let outstanding = Arc::clone(&self.outstanding);
+        let done = Arc::clone(&self.done);
+        let panic = Arc::clone(&self.panic);
+
+        let job: Func<'scope> = Box::new(move || {
+            if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(f)) {
+                // Only keep the first panic; the rest are dropped, matching
+                // how a single `panic::resume_unwind` can only re-raise one.
+                panic.lock().unwrap().get_or_insert(payload);
+            }
+            if outstanding.fetch_sub(1, Ordering::AcqRel) == 1 {
+                let (lock, cvar) = &*done;
+                let _guard = lock.lock().unwrap();
+                cvar.notify_all();
+            }
+        });
+        // SAFETY: `wait` (called from `Registry::scope` before it returns)
+        // blocks until `outstanding` reaches zero, i.e. until this job has
+        // run to completion, so the 'scope data it borrows is still alive
+        // for as long as the job can observe it despite the 'static cast.
+        let job: Func<'static> = unsafe { std::mem::transmute(job) };
+        self.registry.global.push(Job::NewJob(job));
+        self.registry.sleep.notify_work();
+    }
+
+    fn wait(&self) {
+        let (lock, cvar) = &*self.done;
+        let guard = lock.lock().unwrap();
+        let _guard = cvar
+            .wait_while(guard, |_| self.outstanding.load(Ordering::Acquire) != 0)
+            .unwrap();
     }
-    
 }
 impl Drop for Registry {
     fn drop(&mut self) {
         trace!("Closing threadpool");
         self.global.push(Job::Terminate);
+        self.sleep.wake_all();
         for thread in &mut self.threads {
             thread.join();
         }
@@ -82,15 +318,22 @@ struct WorkerThread {
     global: Arc<Injector<Job>>,
     worker: Mutex<Worker<Job>>,
     stealers: RwLock<Vec<Stealer<Job>>>,
+    sleep: Arc<Sleep>,
+    /// A job destined for this worker alone, set by `Registry::broadcast`.
+    /// Kept outside of `worker`'s stealable deque so it can never be picked
+    /// up by another thread.
+    broadcast_job: Mutex<Option<Func<'static>>>,
 }
 impl WorkerThread {
-    fn new(id: usize, global: Arc<Injector<Job>>) -> WorkerThread {
+    fn new(id: usize, global: Arc<Injector<Job>>, sleep: Arc<Sleep>) -> WorkerThread {
         let worker = Worker::new_fifo();
         WorkerThread {
             id,
             global,
             worker: Mutex::new(worker),
             stealers: RwLock::new(Vec::new()),
+            sleep,
+            broadcast_job: Mutex::new(None),
         }
     }
 
@@ -102,10 +345,23 @@ impl WorkerThread {
         self.stealers.write().unwrap().push(stealer);
     }
 
+    pub(super) fn set_broadcast_job(&self, job: Func<'static>) {
+        *self.broadcast_job.lock().unwrap() = Some(job);
+    }
+
+    fn take_broadcast_job(&self) -> Option<Func<'static>> {
+        self.broadcast_job.lock().unwrap().take()
+    }
+
     fn run(&self) {
         let mut stop = false;
+        let mut rounds = 0usize;
         loop {
-            if let Some(job) = self.pop() {
+            if let Some(job) = self.take_broadcast_job() {
+                rounds = 0;
+                job();
+            } else if let Some(job) = self.pop() {
+                rounds = 0;
                 match job {
                     Job::NewJob(f) => f(),
                     Job::Terminate => {
@@ -113,6 +369,7 @@ impl WorkerThread {
                     }
                 }
             } else if let Some(job) = self.steal() {
+                rounds = 0;
                 match job {
                     Job::NewJob(f) => f(),
                     Job::Terminate => {
@@ -120,6 +377,7 @@ impl WorkerThread {
                     }
                 }
             } else if let Some(job) = self.steal_from_global() {
+                rounds = 0;
                 match job {
                     Job::NewJob(f) => f(),
                     Job::Terminate => {
@@ -129,19 +387,55 @@ impl WorkerThread {
             } else {
                 if stop {
                     self.global.push(Job::Terminate);
+                    self.sleep.wake_all();
                     break;
                 }
-                thread::yield_now();
+                rounds += 1;
+                if rounds < ROUNDS_UNTIL_SLEEPY {
+                    thread::yield_now();
+                } else {
+                    let last_event = self.sleep.event_counter();
+                    self.sleep.sleep(last_event, || self.is_empty());
+                    rounds = 0;
+                }
             }
         }
     }
 
+    /// Used by the sleep subsystem's final re-check: true when this worker
+    /// has nothing left to pop or steal.
+    fn is_empty(&self) -> bool {
+        self.broadcast_job.lock().unwrap().is_none()
+            && self.pop_peek().is_none()
+            && self.steal_peek().is_none()
+            && self.steal_from_global_peek().is_none()
+    }
+
+    fn pop_peek(&self) -> Option<()> {
+        if self.worker.lock().unwrap().is_empty() { None } else { Some(()) }
+    }
+
+    fn steal_peek(&self) -> Option<()> {
+        let stealers = self.stealers.read().unwrap();
+        for stealer in stealers.iter() {
+            if !stealer.is_empty() {
+                return Some(());
+            }
+        }
+        None
+    }
+
+    fn steal_from_global_peek(&self) -> Option<()> {
+        if self.global.is_empty() { None } else { Some(()) }
+    }
+
     fn pop(&self) -> Option<Job> {
         self.worker.lock().unwrap().pop()
     }
-    
+
     pub(super) fn push(&self, job: Job) {
         self.worker.lock().unwrap().push(job);
+        self.sleep.notify_work();
     }
 
     fn steal(&self) -> Option<Job> {
@@ -237,4 +531,77 @@ mod tests {
         drop(registry);
         assert_eq!(counter.load(Ordering::SeqCst), 1000);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_registry_idle_workers_sleep_and_wake() {
+        // Workers should park after enough idle rounds, and still pick up
+        // work pushed long after they've gone to sleep.
+        let registry = Registry::new(4, false);
+        thread::sleep(Duration::from_millis(50));
+
+        let counter = Arc::new(AtomicUsize::new(0));
+        let counter_copy = Arc::clone(&counter);
+        registry.execute(move || {
+            counter_copy.fetch_add(1, Ordering::SeqCst);
+        });
+
+        thread::sleep(Duration::from_millis(50));
+        drop(registry);
+        assert_eq!(counter.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_broadcast_runs_once_per_worker() {
+        let registry = Registry::new(8, false);
+        let res = registry.broadcast_with_result(|id| id * 2);
+        assert_eq!(res, vec![0, 2, 4, 6, 8, 10, 12, 14]);
+    }
+
+    #[test]
+    fn test_broadcast_does_not_block_execute() {
+        let registry = Registry::new(4, false);
+        let touched = Arc::new(AtomicUsize::new(0));
+
+        registry.broadcast({
+            let touched = Arc::clone(&touched);
+            move |_id| {
+                touched.fetch_add(1, Ordering::SeqCst);
+            }
+        });
+
+        assert_eq!(touched.load(Ordering::SeqCst), 4);
+
+        let counter = Arc::new(AtomicUsize::new(0));
+        let counter_copy = Arc::clone(&counter);
+        registry.execute(move || {
+            counter_copy.fetch_add(1, Ordering::SeqCst);
+        });
+        thread::sleep(Duration::from_millis(50));
+        drop(registry);
+        assert_eq!(counter.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_scope_allows_borrowed_data() {
+        let registry = Registry::new(4, false);
+        let mut values = vec![0i32; 100];
+
+        registry.scope(|s| {
+            for v in values.iter_mut() {
+                s.spawn(move || *v += 1);
+            }
+        });
+
+        drop(registry);
+        assert_eq!(values, vec![1; 100]);
+    }
+
+    #[test]
+    #[should_panic(expected = "job panicked")]
+    fn test_scope_propagates_panics() {
+        let registry = Registry::new(2, false);
+        registry.scope(|s| {
+            s.spawn(|| panic!("job panicked"));
+        });
+    }
+}
@@ -0,0 +1,338 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::marker::PhantomData;
+
+use crate::node::{in_node::In, inout_node::InOut, out_node::Out};
+
+/// A source template that yields pipeline inputs one at a time from any
+/// `Iterator`.
+pub struct SourceIter<I: Iterator> {
+    iter: I,
+}
+impl<I: Iterator + Send> SourceIter<I> {
+    /// Build a new `SourceIter` around `iter`.
+    pub fn build(iter: I) -> SourceIter<I> {
+        SourceIter { iter }
+    }
+}
+impl<I: Iterator + Send> Out<I::Item> for SourceIter<I> {
+    fn run(&mut self) -> Option<I::Item> {
+        self.iter.next()
+    }
+}
+
+/// A stage template that applies a plain function to every item in the
+/// stream, one in, one out.
+#[derive(Clone)]
+pub struct Sequential<TIn, TOut, F>
+where
+    F: FnMut(TIn) -> TOut,
+{
+    f: F,
+    phantom: PhantomData<(TIn, TOut)>,
+}
+impl<TIn, TOut, F> Sequential<TIn, TOut, F>
+where
+    F: FnMut(TIn) -> TOut,
+{
+    /// Build a new `Sequential` stage around `f`.
+    pub fn build(f: F) -> Sequential<TIn, TOut, F> {
+        Sequential {
+            f,
+            phantom: PhantomData,
+        }
+    }
+}
+impl<TIn: Send, TOut: Send, F> InOut<TIn, TOut> for Sequential<TIn, TOut, F>
+where
+    F: FnMut(TIn) -> TOut + Clone + Send,
+{
+    fn run(&mut self, input: TIn) -> Option<TOut> {
+        Some((self.f)(input))
+    }
+}
+
+/// A sink template that collects every item into a `Vec`, in arrival order.
+pub struct SinkVec<T> {
+    data: Vec<T>,
+}
+impl<T> SinkVec<T> {
+    /// Build a new, empty `SinkVec`.
+    pub fn build() -> SinkVec<T> {
+        SinkVec { data: Vec::new() }
+    }
+}
+impl<T: Send> In<T, Vec<T>> for SinkVec<T> {
+    fn run(&mut self, input: T) {
+        self.data.push(input);
+    }
+    fn finalize(self) -> Option<Vec<T>> {
+        Some(self.data)
+    }
+}
+
+/// A stage template that folds incoming `(K, V)` pairs into a per-replica
+/// `HashMap<K, V>` accumulator using a user-supplied combining function
+/// `f`, flushing that replica's whole partial map downstream once
+/// end-of-stream is reached.
+///
+/// Every time a pair for key `k` arrives, it is combined with whatever
+/// this replica has accumulated for `k` so far (`f(acc, v)`, or just `v`
+/// the first time `k` is seen). Unlike a single-threaded fold, nothing is
+/// emitted as pairs arrive: `run` only updates the local accumulator,
+/// and `finalize` (called by the rts once this replica has observed
+/// `Task::Terminate`) emits its entire partial map in one message.
+///
+/// Runs with a single replica by default; call `with_replicas` to fan
+/// out across more. Dispatch to replicas goes through the same shared
+/// work-stealing queue every other unordered stage uses — it isn't
+/// content-aware, so a given
+/// key isn't guaranteed to always land on the same replica. That's fine
+/// here: correctness doesn't depend on key locality, since every
+/// replica's partial map, however it's split, is combined with the same
+/// `f` by a `ReduceMerge` stage downstream.
+#[derive(Clone)]
+pub struct Reduce<K, V, F>
+where
+    K: Eq + Hash + Clone,
+    V: Clone,
+    F: Fn(V, V) -> V,
+{
+    acc: HashMap<K, V>,
+    combine: F,
+    replicas: usize,
+}
+impl<K, V, F> Reduce<K, V, F>
+where
+    K: Eq + Hash + Clone,
+    V: Clone,
+    F: Fn(V, V) -> V,
+{
+    /// Build a new `Reduce` stage that folds values sharing a key with `f`.
+    pub fn build(f: F) -> Reduce<K, V, F> {
+        Reduce {
+            acc: HashMap::new(),
+            combine: f,
+            replicas: 1,
+        }
+    }
+
+    /// Run this stage with `n` replicas instead of the default single
+    /// replica. Pair with `ReduceMerge` downstream to combine their
+    /// partial maps into one final result.
+    pub fn with_replicas(mut self, n: usize) -> Self {
+        self.replicas = n;
+        self
+    }
+}
+impl<K, V, F> InOut<(K, V), HashMap<K, V>> for Reduce<K, V, F>
+where
+    K: Eq + Hash + Clone + Send,
+    V: Clone + Send,
+    F: Fn(V, V) -> V + Clone + Send,
+{
+    fn run(&mut self, input: (K, V)) -> Option<HashMap<K, V>> {
+        let (k, v) = input;
+        let combined = match self.acc.remove(&k) {
+            Some(old) => (self.combine)(old, v),
+            None => v,
+        };
+        self.acc.insert(k, combined);
+        None
+    }
+
+    fn finalize(&mut self) -> Option<HashMap<K, V>> {
+        Some(std::mem::take(&mut self.acc))
+    }
+
+    fn number_of_replicas(&self) -> usize {
+        self.replicas
+    }
+}
+
+/// A stage template that fuses "map to a `(K, V)` pair" and "fold by key"
+/// into a single node, so a word-count / histogram / group-by stage doesn't
+/// need a separate map stage ahead of a `Reduce`. See `Reduce` for the
+/// per-replica accumulate/flush/merge model this follows.
+#[derive(Clone)]
+pub struct MapReduce<TIn, K, V, FMap, FReduce>
+where
+    K: Eq + Hash + Clone,
+    V: Clone,
+    FMap: Fn(TIn) -> (K, V),
+    FReduce: Fn(V, V) -> V,
+{
+    map: FMap,
+    reduce: Reduce<K, V, FReduce>,
+    phantom: PhantomData<TIn>,
+}
+impl<TIn, K, V, FMap, FReduce> MapReduce<TIn, K, V, FMap, FReduce>
+where
+    K: Eq + Hash + Clone,
+    V: Clone,
+    FMap: Fn(TIn) -> (K, V),
+    FReduce: Fn(V, V) -> V,
+{
+    /// Build a new `MapReduce` stage: `map` turns each input into a keyed
+    /// value, `reduce` folds values sharing a key.
+    pub fn build(map: FMap, reduce: FReduce) -> MapReduce<TIn, K, V, FMap, FReduce> {
+        MapReduce {
+            map,
+            reduce: Reduce::build(reduce),
+            phantom: PhantomData,
+        }
+    }
+
+    /// Run this stage with `n` replicas instead of the default single
+    /// replica; see `Reduce::with_replicas`.
+    pub fn with_replicas(mut self, n: usize) -> Self {
+        self.reduce = self.reduce.with_replicas(n);
+        self
+    }
+}
+impl<TIn, K, V, FMap, FReduce> InOut<TIn, HashMap<K, V>> for MapReduce<TIn, K, V, FMap, FReduce>
+where
+    TIn: Send,
+    K: Eq + Hash + Clone + Send,
+    V: Clone + Send,
+    FMap: Fn(TIn) -> (K, V) + Clone + Send,
+    FReduce: Fn(V, V) -> V + Clone + Send,
+{
+    fn run(&mut self, input: TIn) -> Option<HashMap<K, V>> {
+        let kv = (self.map)(input);
+        self.reduce.run(kv)
+    }
+
+    fn finalize(&mut self) -> Option<HashMap<K, V>> {
+        self.reduce.finalize()
+    }
+
+    fn number_of_replicas(&self) -> usize {
+        self.reduce.number_of_replicas()
+    }
+}
+
+/// A merge stage that combines the partial `HashMap<K, V>` maps flushed by
+/// `Reduce`/`MapReduce`'s replicas (one per replica, emitted via
+/// `finalize` at end-of-stream) into a single running total, using the
+/// same combining function `f` for any key seen in more than one partial
+/// map. Always runs as a single replica: maintaining one running total
+/// across every upstream replica's partial map needs a single point of
+/// accumulation.
+///
+/// Unlike `Reduce`, every incoming partial map immediately re-emits the
+/// whole running total rather than withholding output until its own
+/// end-of-stream; a `SinkReduce` downstream keeps only the most recent
+/// emission, which is exactly the fully merged result once every
+/// replica's partial map has arrived.
+#[derive(Clone)]
+pub struct ReduceMerge<K, V, F>
+where
+    K: Eq + Hash + Clone,
+    V: Clone,
+    F: Fn(V, V) -> V,
+{
+    acc: HashMap<K, V>,
+    combine: F,
+}
+impl<K, V, F> ReduceMerge<K, V, F>
+where
+    K: Eq + Hash + Clone,
+    V: Clone,
+    F: Fn(V, V) -> V,
+{
+    /// Build a new `ReduceMerge` stage that merges partial maps with `f`.
+    pub fn build(f: F) -> ReduceMerge<K, V, F> {
+        ReduceMerge {
+            acc: HashMap::new(),
+            combine: f,
+        }
+    }
+}
+impl<K, V, F> InOut<HashMap<K, V>, HashMap<K, V>> for ReduceMerge<K, V, F>
+where
+    K: Eq + Hash + Clone + Send,
+    V: Clone + Send,
+    F: Fn(V, V) -> V + Clone + Send,
+{
+    fn run(&mut self, input: HashMap<K, V>) -> Option<HashMap<K, V>> {
+        for (k, v) in input {
+            let combined = match self.acc.remove(&k) {
+                Some(old) => (self.combine)(old, v),
+                None => v,
+            };
+            self.acc.insert(k, combined);
+        }
+        Some(self.acc.clone())
+    }
+}
+
+/// A sink template that collects the result of a `Reduce`/`MapReduce`
+/// stage (optionally merged first by `ReduceMerge`) into a final
+/// `HashMap<K, V>`. Each incoming map overwrites the running result
+/// outright rather than being merged key-by-key, so this only produces a
+/// correct total when every item it receives is itself already a
+/// complete, self-contained map — a single-replica `Reduce`'s one partial
+/// map, or `ReduceMerge`'s cumulative running total, both qualify; a
+/// multi-replica `Reduce` wired directly here without a `ReduceMerge`
+/// between them would not.
+pub struct SinkReduce<K: Eq + Hash, V> {
+    map: HashMap<K, V>,
+}
+impl<K: Eq + Hash, V> SinkReduce<K, V> {
+    /// Build a new, empty `SinkReduce`.
+    pub fn build() -> SinkReduce<K, V> {
+        SinkReduce { map: HashMap::new() }
+    }
+}
+impl<K: Eq + Hash + Send, V: Send> In<HashMap<K, V>, HashMap<K, V>> for SinkReduce<K, V> {
+    fn run(&mut self, input: HashMap<K, V>) {
+        self.map = input;
+    }
+    fn finalize(self) -> Option<HashMap<K, V>> {
+        Some(self.map)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Drives the same accumulate/finalize/merge flow `rts` does for a
+    // multi-replica `Reduce -> ReduceMerge` pipeline, but by hand: one
+    // `Reduce` per simulated replica gets a disjoint slice of the input,
+    // each is `finalize`d into its own partial map, and those partial maps
+    // are fed into a single `ReduceMerge` the way the real pipeline feeds
+    // it one partial map per upstream replica. Confirms the partial maps
+    // combine into the same total a single-replica reduction would produce.
+    #[test]
+    fn reduce_merge_combines_partial_maps_from_multiple_replicas() {
+        let words = ["a", "b", "a", "c", "b", "a", "d", "c", "a", "b", "e", "a"];
+
+        let mut replica_a = Reduce::<&str, i32, _>::build(|a, b| a + b);
+        let mut replica_b = Reduce::<&str, i32, _>::build(|a, b| a + b);
+
+        for (i, w) in words.iter().enumerate() {
+            if i % 2 == 0 {
+                replica_a.run((*w, 1));
+            } else {
+                replica_b.run((*w, 1));
+            }
+        }
+
+        let partial_a = replica_a.finalize().unwrap();
+        let partial_b = replica_b.finalize().unwrap();
+
+        let mut merge = ReduceMerge::build(|a: i32, b: i32| a + b);
+        merge.run(partial_a);
+        let merged = merge.run(partial_b).unwrap();
+
+        let mut expected: HashMap<&str, i32> = HashMap::new();
+        for w in words.iter() {
+            *expected.entry(w).or_insert(0) += 1;
+        }
+
+        assert_eq!(merged, expected);
+    }
+}
+
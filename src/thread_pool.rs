@@ -1,22 +1,91 @@
 use crossbeam_deque::{Injector, Stealer, Worker, Steal};
 use log::trace;
+use std::any::Any;
 use std::collections::BTreeMap;
 use std::error::Error;
 use std::marker::PhantomData;
-use std::sync::atomic::AtomicUsize;
-use std::sync::{Arc, Barrier, RwLock, Mutex};
-use std::{fmt, hint, iter, mem};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Barrier, Condvar, RwLock, Mutex};
+use std::time::{Duration, Instant};
+use std::{fmt, hint, iter, mem, thread};
 
-use crate::channel::channel::Channel;
+use crate::channel::channel::{Channel, Receiver};
 use crate::core::orchestrator::{get_global_orchestrator, JobInfo, Orchestrator};
 
 type Func<'a> = Box<dyn FnOnce() + Send + 'a>;
+/// Called with the payload of a job that panicked, via
+/// `ThreadPool::with_panic_handler`.
+type PanicHandler = Arc<dyn Fn(Box<dyn Any + Send>) + Send + Sync>;
+
+/// Rounds of `hint::spin_loop()` an idle worker tries before falling back
+/// to `thread::yield_now()`.
+const SPIN_ROUNDS: u32 = 32;
+/// Further rounds of `thread::yield_now()` an idle worker tries before
+/// parking on `SleepState`'s condvar.
+const YIELD_ROUNDS: u32 = 32;
+
+/// Default number of local jobs a worker processes between checks of the
+/// global injector; see `ThreadPoolBuilder::fairness_jobs`.
+const DEFAULT_FAIRNESS_JOBS: usize = 100;
+/// Default time a worker lets pass between checks of the global injector;
+/// see `ThreadPoolBuilder::fairness_interval`.
+const DEFAULT_FAIRNESS_INTERVAL: Duration = Duration::from_millis(1);
 
 enum Job {
     NewJob(Func<'static>),
     Terminate,
 }
 
+/// Shared wake-up mechanism so idle workers (and `ThreadPool::wait`) can
+/// park instead of busy-spinning. `jobs_counter` is bumped by every
+/// producer of a state change worth re-checking (`execute`, `Scope::execute`
+/// pushing a job, and `ThreadPoolWorker::task_done` finishing one), and a
+/// waiter re-samples it under `mutex` immediately before calling
+/// `condvar.wait` so a change that happened during the spin/yield phase is
+/// never missed.
+struct SleepState {
+    mutex: Mutex<()>,
+    condvar: Condvar,
+    jobs_counter: AtomicU64,
+}
+impl SleepState {
+    fn new() -> SleepState {
+        SleepState {
+            mutex: Mutex::new(()),
+            condvar: Condvar::new(),
+            jobs_counter: AtomicU64::new(0),
+        }
+    }
+
+    fn counter(&self) -> u64 {
+        self.jobs_counter.load(Ordering::SeqCst)
+    }
+
+    fn notify_one(&self) {
+        self.jobs_counter.fetch_add(1, Ordering::SeqCst);
+        let _guard = self.mutex.lock().unwrap();
+        self.condvar.notify_one();
+    }
+
+    fn notify_all(&self) {
+        self.jobs_counter.fetch_add(1, Ordering::SeqCst);
+        let _guard = self.mutex.lock().unwrap();
+        self.condvar.notify_all();
+    }
+
+    /// Block until `jobs_counter` moves past `last`, re-sampling it under
+    /// `mutex` first so a bump that raced with the caller's last check
+    /// isn't missed.
+    fn wait_for_change(&self, last: u64) {
+        let guard = self.mutex.lock().unwrap();
+        let _guard = self
+            .condvar
+            .wait_while(guard, |_| self.jobs_counter.load(Ordering::SeqCst) == last)
+            .unwrap();
+    }
+}
+
 #[derive(Debug)]
 pub struct ThreadPoolError {
     details: String,
@@ -42,37 +111,161 @@ impl Error for ThreadPoolError {
     }
 }
 
+/// Error returned by `TaskHandle::join`/`try_join` when the task submitted
+/// via `ThreadPool::submit` panicked instead of returning a value.
+#[derive(Debug)]
+pub struct TaskPanicked;
+
+impl fmt::Display for TaskPanicked {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "submitted task panicked")
+    }
+}
+
+impl Error for TaskPanicked {}
+
+type TaskSlot<R> = Arc<(Mutex<Option<Result<R, TaskPanicked>>>, Condvar)>;
+
+/// Handle to a task submitted via `ThreadPool::submit`, analogous to a
+/// sync/async client split: `join` is the synchronous path, blocking until
+/// the result is ready; `try_join` is the asynchronous one, polling without
+/// blocking.
+pub struct TaskHandle<R> {
+    slot: TaskSlot<R>,
+}
+
+impl<R> TaskHandle<R> {
+    /// Block until the task finishes, returning its value, or `Err` if the
+    /// closure panicked instead of returning normally.
+    pub fn join(self) -> Result<R, TaskPanicked> {
+        let (lock, cvar) = &*self.slot;
+        let mut guard = lock.lock().unwrap();
+        loop {
+            if let Some(result) = guard.take() {
+                return result;
+            }
+            guard = cvar.wait(guard).unwrap();
+        }
+    }
+
+    /// Return the task's result without blocking if it has already
+    /// finished, or `None` if it's still running. The result is handed out
+    /// only once: after `try_join` has returned it, later `try_join` calls
+    /// return `None` and `join` blocks forever.
+    pub fn try_join(&self) -> Option<Result<R, TaskPanicked>> {
+        self.slot.0.lock().unwrap().take()
+    }
+}
+
 
 
 // Struct representing a worker in the thread pool.
 struct ThreadPoolWorker {
     id: usize,
+    // A Chase-Lev work-stealing deque: `worker` pushes/pops its own "bottom"
+    // end (wrapped in a `Mutex` only because `push`/`wait`-time replacement
+    // share `&self`; the deque itself needs no lock for the owner's own
+    // single-threaded push/pop pattern), while peers steal lock-free from
+    // the "top" via a `Stealer` (see `stealer`). Built LIFO so the owner's
+    // own `pop` takes its most recently pushed task first — cache-friendly
+    // for a recursive divide-and-conquer workload like `join`, which keeps
+    // re-pushing and immediately popping its own freshly spawned subtasks —
+    // while a thief's `steal` still takes the oldest (largest, coarsest)
+    // task from the opposite end, which is exactly the task worth stealing.
     worker: Mutex<Worker<Job>>,
     stealers: RwLock<Vec<Stealer<Job>>>,
     global: Arc<Injector<Job>>,
     total_tasks: Arc<AtomicUsize>,
+    sleep: Arc<SleepState>,
+    panic_handler: Arc<Mutex<Option<PanicHandler>>>,
+    /// xorshift64 state seeded from `id`, used by `steal` to pick a random
+    /// starting victim instead of always scanning peers in the same order
+    /// (which would otherwise funnel every idle worker's first steal
+    /// attempt onto the same unlucky peer).
+    rng_state: AtomicU64,
+    /// A job destined for this worker alone, set by `ThreadPool::broadcast`.
+    /// Kept outside of `worker`'s stealable deque so it can never be picked
+    /// up by another thread and run twice (or zero times).
+    broadcast_job: Mutex<Option<Func<'static>>>,
+    // Fairness policy (see `ThreadPoolBuilder`): how many local jobs to run
+    // and how much time may pass before `fetch_task` checks the global
+    // injector ahead of the local queue, so an external `execute`d job
+    // can't be starved by a worker that keeps generating its own subtasks.
+    fairness_jobs: usize,
+    fairness_interval: Duration,
+    jobs_since_global_check: AtomicUsize,
+    last_global_check: Mutex<Instant>,
 }
 impl ThreadPoolWorker {
-    fn new(id: usize, global: Arc<Injector<Job>>, total_tasks: Arc<AtomicUsize>) -> Self {
-        let worker = Mutex::new(Worker::new_fifo());
+    fn new(
+        id: usize,
+        global: Arc<Injector<Job>>,
+        total_tasks: Arc<AtomicUsize>,
+        sleep: Arc<SleepState>,
+        panic_handler: Arc<Mutex<Option<PanicHandler>>>,
+        fairness_jobs: usize,
+        fairness_interval: Duration,
+    ) -> Self {
+        let worker = Mutex::new(Worker::new_lifo());
         let stealers = RwLock::new(Vec::new());
+        // Seed must be non-zero for xorshift64 to ever produce anything but
+        // zero; mix `id` so sibling workers don't start in lockstep.
+        let rng_state = AtomicU64::new((id as u64 + 1).wrapping_mul(0x9E3779B97F4A7C15));
         Self {
             id,
             worker,
             stealers,
             global,
             total_tasks,
-
+            sleep,
+            panic_handler,
+            rng_state,
+            broadcast_job: Mutex::new(None),
+            fairness_jobs,
+            fairness_interval,
+            jobs_since_global_check: AtomicUsize::new(0),
+            last_global_check: Mutex::new(Instant::now()),
         }
     }
 
+    /// Next xorshift64 value from this worker's `rng_state`; see its docs.
+    fn next_random(&self) -> u64 {
+        let mut x = self.rng_state.load(Ordering::Relaxed);
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.rng_state.store(x, Ordering::Relaxed);
+        x
+    }
+
+    fn set_broadcast_job(&self, job: Func<'static>) {
+        *self.broadcast_job.lock().unwrap() = Some(job);
+    }
+
+    fn take_broadcast_job(&self) -> Option<Func<'static>> {
+        self.broadcast_job.lock().unwrap().take()
+    }
+
 
     // Fetch a task. If the local queue is empty, try to steal a batch of tasks from the global queue.
     // If the global queue is empty, try to steal a task from one of the other threads.
     fn fetch_task(&self) -> Option<Job> {
+        // Fairness: a worker that keeps generating its own subtasks would
+        // otherwise drain its local queue forever and starve externally
+        // `execute`d jobs sitting in the global injector. Periodically jump
+        // the queue and check the injector first instead.
+        if self.due_for_global_check() {
+            self.mark_global_checked();
+            if let Some(job) = self.steal_from_global() {
+                return Some(job);
+            }
+        }
+
         if let Some(job) = self.pop() {
+            self.jobs_since_global_check.fetch_add(1, Ordering::Relaxed);
             return Some(job);
         } else if let Some(job) = self.steal_from_global() {
+            self.mark_global_checked();
             return Some(job);
         } else if let Some(job) = self.steal() {
             return Some(job);
@@ -80,24 +273,83 @@ impl ThreadPoolWorker {
         None
     }
 
+    fn due_for_global_check(&self) -> bool {
+        if self.jobs_since_global_check.load(Ordering::Relaxed) >= self.fairness_jobs {
+            return true;
+        }
+        self.last_global_check.lock().unwrap().elapsed() >= self.fairness_interval
+    }
+
+    fn mark_global_checked(&self) {
+        self.jobs_since_global_check.store(0, Ordering::Relaxed);
+        *self.last_global_check.lock().unwrap() = Instant::now();
+    }
+
+    /// Run a fetched job, returning `true` if it was a `Terminate` marker.
+    fn run_job(&self, job: Job) -> bool {
+        match job {
+            Job::NewJob(func) => {
+                // Isolate a panicking job: always account for its
+                // completion so `total_tasks` never leaks a count that
+                // would otherwise leave `wait()` hanging forever, and hand
+                // the payload to the user's panic handler, if any, instead
+                // of letting it unwind (and kill) this worker thread.
+                let result = panic::catch_unwind(AssertUnwindSafe(func));
+                self.task_done();
+                if let Err(payload) = result {
+                    if let Some(handler) = self.panic_handler.lock().unwrap().as_ref() {
+                        handler(payload);
+                    }
+                }
+                false
+            }
+            Job::Terminate => true,
+        }
+    }
+
     /// This is the main loop of the thread.
     fn run(&self) {
         let mut stop = false;
+        let mut idle_rounds = 0u32;
         loop {
-            let res = self.fetch_task();
-            match res {
-                Some(task) => match task {
-                    Job::NewJob(func) => {
-                        (func)();
-                        self.task_done();
+            if let Some(job) = self.take_broadcast_job() {
+                idle_rounds = 0;
+                job();
+                continue;
+            }
+            match self.fetch_task() {
+                Some(task) => {
+                    idle_rounds = 0;
+                    if self.run_job(task) {
+                        stop = true;
                     }
-                    Job::Terminate => stop = true,
-                },
+                }
                 None => {
                     if stop {
                         break;
+                    }
+                    idle_rounds += 1;
+                    if idle_rounds <= SPIN_ROUNDS {
+                        hint::spin_loop();
+                    } else if idle_rounds <= SPIN_ROUNDS + YIELD_ROUNDS {
+                        thread::yield_now();
                     } else {
-                        continue;
+                        let last = self.sleep.counter();
+                        // Final re-check under the counter snapshot: a job
+                        // pushed during the spin/yield phase must still be
+                        // picked up instead of being slept through.
+                        match self.fetch_task() {
+                            Some(task) => {
+                                idle_rounds = 0;
+                                if self.run_job(task) {
+                                    stop = true;
+                                }
+                            }
+                            None => {
+                                self.sleep.wait_for_change(last);
+                                idle_rounds = 0;
+                            }
+                        }
                     }
                 }
             }
@@ -116,10 +368,18 @@ impl ThreadPoolWorker {
         worker.pop()
     }
 
-    // Steal a job from another worker.
+    // Steal a job from another worker, starting from a randomly chosen
+    // victim so idle workers don't all pile onto the same first peer, then
+    // sweeping the rest in case the random pick came up empty.
     fn steal(&self) -> Option<Job> {
         let stealers = self.stealers.read().unwrap();
-        for stealer in stealers.iter() {
+        let len = stealers.len();
+        if len == 0 {
+            return None;
+        }
+        let start = (self.next_random() as usize) % len;
+        for offset in 0..len {
+            let stealer = &stealers[(start + offset) % len];
             loop {
                 match stealer.steal() {
                     Steal::Success(job) => return Some(job),
@@ -163,34 +423,111 @@ impl ThreadPoolWorker {
 
     // Warn task done.
     fn task_done(&self) {
-        self.total_tasks.fetch_sub(1, std::sync::atomic::Ordering::AcqRel);
+        self.total_tasks.fetch_sub(1, Ordering::AcqRel);
+        // Wake `ThreadPool::wait`, which parks on the same SleepState.
+        self.sleep.notify_all();
     }
 
 }
+/// Builder for a `ThreadPool` with a configurable fairness policy between
+/// the global injector and each worker's local queue. Without this, a
+/// worker generating its own subtasks could drain its local queue forever
+/// and starve externally `execute`d jobs; tune `fairness_jobs`/
+/// `fairness_interval` down for latency-sensitive workloads that need
+/// external submissions serviced more aggressively, or up to favor local
+/// throughput.
+pub struct ThreadPoolBuilder {
+    num_threads: usize,
+    fairness_jobs: usize,
+    fairness_interval: Duration,
+}
+
+impl ThreadPoolBuilder {
+    pub fn new(num_threads: usize) -> ThreadPoolBuilder {
+        ThreadPoolBuilder {
+            num_threads,
+            fairness_jobs: DEFAULT_FAIRNESS_JOBS,
+            fairness_interval: DEFAULT_FAIRNESS_INTERVAL,
+        }
+    }
+
+    /// How many local jobs a worker runs before checking the global
+    /// injector ahead of its local queue. Default 100.
+    pub fn fairness_jobs(mut self, jobs: usize) -> ThreadPoolBuilder {
+        self.fairness_jobs = jobs;
+        self
+    }
+
+    /// How much time may pass before a worker checks the global injector
+    /// ahead of its local queue. Default 1ms.
+    pub fn fairness_interval(mut self, interval: Duration) -> ThreadPoolBuilder {
+        self.fairness_interval = interval;
+        self
+    }
+
+    pub fn build(self) -> ThreadPool {
+        let orchestrator = get_global_orchestrator();
+        ThreadPool::with_fairness(
+            self.num_threads,
+            orchestrator,
+            self.fairness_jobs,
+            self.fairness_interval,
+        )
+    }
+}
+
 ///Struct representing a thread pool.
 pub struct ThreadPool {
     jobs_info: Vec<JobInfo>,
-    workers: Vec<Arc<ThreadPoolWorker>>,
+    // Held behind a lock so a dead worker's thread can be swapped out for a
+    // freshly respawned one without needing `&mut self`.
+    workers: Arc<RwLock<Vec<Arc<ThreadPoolWorker>>>>,
     total_tasks: Arc<AtomicUsize>,
     injector: Arc<Injector<Job>>,
     orchestrator: Arc<Orchestrator>,
+    sleep: Arc<SleepState>,
+    panic_handler: Arc<Mutex<Option<PanicHandler>>>,
+    fairness_jobs: usize,
+    fairness_interval: Duration,
 }
 
 impl Clone for ThreadPool {
-    /// Create a new threadpool from an existing one, using the same number of threads.
+    /// Create a new threadpool from an existing one, using the same number
+    /// of threads and the same fairness policy.
     fn clone(&self) -> Self {
         let orchestrator = self.orchestrator.clone();
-        ThreadPool::new(self.workers.len(), orchestrator)
+        ThreadPool::with_fairness(
+            self.workers.read().unwrap().len(),
+            orchestrator,
+            self.fairness_jobs,
+            self.fairness_interval,
+        )
     }
 }
 
 impl ThreadPool {
     fn new(num_threads: usize, orchestrator: Arc<Orchestrator>) -> Self {
+        Self::with_fairness(
+            num_threads,
+            orchestrator,
+            DEFAULT_FAIRNESS_JOBS,
+            DEFAULT_FAIRNESS_INTERVAL,
+        )
+    }
+
+    fn with_fairness(
+        num_threads: usize,
+        orchestrator: Arc<Orchestrator>,
+        fairness_jobs: usize,
+        fairness_interval: Duration,
+    ) -> Self {
         trace!("Creating new threadpool");
         let jobs_info;
         let mut workers = Vec::with_capacity(num_threads);
 
         let total_tasks = Arc::new(AtomicUsize::new(0));
+        let sleep = Arc::new(SleepState::new());
+        let panic_handler: Arc<Mutex<Option<PanicHandler>>> = Arc::new(Mutex::new(None));
         let barrier = Arc::new(Barrier::new(num_threads));
         let mut funcs = Vec::new();
 
@@ -199,7 +536,17 @@ impl ThreadPool {
         for i in 0..num_threads {
             let global = Arc::clone(&injector);
             let total_tasks_cp = Arc::clone(&total_tasks);
-            let worker = ThreadPoolWorker::new(i, global, total_tasks_cp);
+            let sleep_cp = Arc::clone(&sleep);
+            let panic_handler_cp = Arc::clone(&panic_handler);
+            let worker = ThreadPoolWorker::new(
+                i,
+                global,
+                total_tasks_cp,
+                sleep_cp,
+                panic_handler_cp,
+                fairness_jobs,
+                fairness_interval,
+            );
             workers.push(Arc::new(worker));
         }
 
@@ -217,12 +564,27 @@ impl ThreadPool {
             }
         }
 
-        for worker in &workers {
+        let workers = Arc::new(RwLock::new(workers));
+
+        for i in 0..num_threads {
             let barrier = Arc::clone(&barrier);
-            let worker = Arc::clone(&worker);
+            let workers = Arc::clone(&workers);
+            let global = Arc::clone(&injector);
+            let total_tasks_cp = Arc::clone(&total_tasks);
+            let sleep_cp = Arc::clone(&sleep);
+            let panic_handler_cp = Arc::clone(&panic_handler);
             let func = move || {
                 barrier.wait();
-                worker.run();
+                Self::run_worker_with_replenishment(
+                    i,
+                    &workers,
+                    &global,
+                    &total_tasks_cp,
+                    &sleep_cp,
+                    &panic_handler_cp,
+                    fairness_jobs,
+                    fairness_interval,
+                );
             };
             funcs.push(Box::new(func));
         }
@@ -235,6 +597,10 @@ impl ThreadPool {
             total_tasks,
             injector,
             orchestrator,
+            sleep,
+            panic_handler,
+            fairness_jobs,
+            fairness_interval,
         }
     }
 
@@ -243,24 +609,319 @@ impl ThreadPool {
         Self::new(num_threads, orchestrator)
     }
 
+    /// Begin building a `ThreadPool` with a non-default fairness policy
+    /// between the global injector and each worker's local queue; see
+    /// `ThreadPoolBuilder`.
+    pub fn builder(num_threads: usize) -> ThreadPoolBuilder {
+        ThreadPoolBuilder::new(num_threads)
+    }
+
+    /// Opt in to observing captured job panics instead of having them
+    /// silently swallowed by `run_job`'s `catch_unwind`.
+    pub fn with_panic_handler<F>(self, handler: F) -> Self
+    where
+        F: Fn(Box<dyn Any + Send>) + Send + Sync + 'static,
+    {
+        *self.panic_handler.lock().unwrap() = Some(Arc::new(handler));
+        self
+    }
+
+    /// Run worker `id`'s loop, and if it unwinds anyway (e.g. a poisoned
+    /// lock, rather than a job panic already isolated by `run_job`'s
+    /// `catch_unwind`), rebuild that worker's state, re-register it with
+    /// its peers so stealing keeps working, and keep the thread alive
+    /// running the replacement instead of silently shrinking the pool, as
+    /// the `executors` crate's work-stealing pool does.
+    fn run_worker_with_replenishment(
+        id: usize,
+        workers: &Arc<RwLock<Vec<Arc<ThreadPoolWorker>>>>,
+        global: &Arc<Injector<Job>>,
+        total_tasks: &Arc<AtomicUsize>,
+        sleep: &Arc<SleepState>,
+        panic_handler: &Arc<Mutex<Option<PanicHandler>>>,
+        fairness_jobs: usize,
+        fairness_interval: Duration,
+    ) {
+        loop {
+            let worker = Arc::clone(&workers.read().unwrap()[id]);
+            let result = panic::catch_unwind(AssertUnwindSafe(|| worker.run()));
+            if result.is_ok() {
+                return;
+            }
+
+            if let Some(handler) = panic_handler.lock().unwrap().as_ref() {
+                if let Err(payload) = result {
+                    handler(payload);
+                }
+            }
+            trace!("worker {} died, respawning", id);
+
+            let fresh = Arc::new(ThreadPoolWorker::new(
+                id,
+                Arc::clone(global),
+                Arc::clone(total_tasks),
+                Arc::clone(sleep),
+                Arc::clone(panic_handler),
+                fairness_jobs,
+                fairness_interval,
+            ));
+            {
+                let guard = workers.read().unwrap();
+                for (other_id, other) in guard.iter().enumerate() {
+                    if other_id != id {
+                        other.add_stealer(fresh.stealer());
+                        fresh.add_stealer(other.stealer());
+                    }
+                }
+            }
+            workers.write().unwrap()[id] = fresh;
+            // The dead worker's old deque may still hold jobs, which peers
+            // can still steal through the stealers they already hold; wake
+            // them so they notice.
+            sleep.notify_all();
+        }
+    }
+
     /// Execute a function `task` on a thread in the thread pool.
     pub fn execute<F>(&self, task: F)
     where
         F: FnOnce() + Send + 'static,
     {
         self.injector.push(Job::NewJob(Box::new(task)));
-        self.total_tasks
-            .fetch_add(1, std::sync::atomic::Ordering::AcqRel);
+        self.total_tasks.fetch_add(1, Ordering::AcqRel);
+        self.sleep.notify_one();
+    }
+
+    /// Like `execute`, but returns a `TaskHandle` instead of discarding the
+    /// result: `join` it for a synchronous wait, or poll it with `try_join`
+    /// for an asynchronous check. A panic inside `task` is caught and
+    /// reported through the handle rather than the pool's `panic_handler`.
+    pub fn submit<F, R>(&self, task: F) -> TaskHandle<R>
+    where
+        F: FnOnce() -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        let slot: TaskSlot<R> = Arc::new((Mutex::new(None), Condvar::new()));
+        let slot_cp = Arc::clone(&slot);
+
+        self.execute(move || {
+            let result = panic::catch_unwind(AssertUnwindSafe(task)).map_err(|_| TaskPanicked);
+            let (lock, cvar) = &*slot_cp;
+            *lock.lock().unwrap() = Some(result);
+            cvar.notify_all();
+        });
+
+        TaskHandle { slot }
     }
 
     /// Block until all current jobs in the thread pool are finished.
     pub fn wait(&self) {
-        while (self.total_tasks.load(std::sync::atomic::Ordering::Acquire) != 0)
-            || !self.injector.is_empty()
-        {
-            hint::spin_loop();
+        loop {
+            if self.total_tasks.load(Ordering::Acquire) == 0 && self.injector.is_empty() {
+                return;
+            }
+            let last = self.sleep.counter();
+            if self.total_tasks.load(Ordering::Acquire) == 0 && self.injector.is_empty() {
+                return;
+            }
+            self.sleep.wait_for_change(last);
         }
     }
+
+    /// Fork-join: run `a` on the calling thread while `b` is handed to the
+    /// pool, then return `(a(), b())`.
+    ///
+    /// `b` is pushed onto the global injector so any idle worker can steal
+    /// it while `a` runs locally. Once `a` finishes, the calling thread
+    /// itself joins in stealing from the pool rather than just blocking, so
+    /// a `join` issued from inside a worker's own job still makes progress
+    /// instead of deadlocking the pool waiting on its own queue.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pspp::thread_pool::ThreadPool;
+    ///
+    /// let pool = ThreadPool::new_with_global_registry(8);
+    /// let (a, b) = pool.join(|| 1 + 1, || 2 + 2);
+    /// assert_eq!((a, b), (2, 4));
+    /// ```
+    pub fn join<'scope, A, B, RA, RB>(&self, a: A, b: B) -> (RA, RB)
+    where
+        A: FnOnce() -> RA + Send + 'scope,
+        B: FnOnce() -> RB + Send + 'scope,
+        RA: Send,
+        RB: Send,
+    {
+        let done = Arc::new((Mutex::new(None::<RB>), Condvar::new()));
+        let done_cp = Arc::clone(&done);
+
+        // Safety: the unsafe cast erases `'scope` so the closure can be
+        // boxed as `Func<'static>` and pushed onto the injector. This is
+        // sound because this function blocks below until `b`'s result is
+        // recorded in `done`, which cannot happen before `b` has finished
+        // running, so nothing captured by `b` is ever accessed after
+        // `'scope` ends.
+        let b_job: Func<'static> = unsafe {
+            mem::transmute::<Func<'scope>, Func<'static>>(Box::new(move || {
+                let result = b();
+                let (lock, cvar) = &*done_cp;
+                let mut slot = lock.lock().unwrap();
+                *slot = Some(result);
+                cvar.notify_all();
+            }))
+        };
+
+        self.injector.push(Job::NewJob(b_job));
+        self.total_tasks.fetch_add(1, Ordering::AcqRel);
+        self.sleep.notify_one();
+
+        // `b_job` is only sound to run past `'scope` for as long as this
+        // function is guaranteed to block until it's done (see the safety
+        // comment above). If `a` panics, unwinding straight out of this
+        // function would break that guarantee while `b_job` may still be
+        // running on another thread and touching `'scope`-borrowed data.
+        // So the panic is caught here and only re-raised once the drain
+        // loop below has confirmed `b_job` finished.
+        let a_result = panic::catch_unwind(AssertUnwindSafe(a));
+
+        loop {
+            {
+                let (lock, _) = &*done;
+                if lock.lock().unwrap().is_some() {
+                    break;
+                }
+            }
+            // Help drain the pool instead of just blocking, so a join
+            // called from inside a worker cannot deadlock.
+            match self.steal_any() {
+                Some(Job::NewJob(f)) => {
+                    // Isolate a panicking stolen job exactly like `run_job`
+                    // does: always account for its completion so
+                    // `total_tasks` never leaks, and hand the payload to
+                    // the panic handler instead of letting it unwind into
+                    // this `join` call.
+                    let result = panic::catch_unwind(AssertUnwindSafe(f));
+                    self.total_tasks.fetch_sub(1, Ordering::AcqRel);
+                    self.sleep.notify_all();
+                    if let Err(payload) = result {
+                        if let Some(handler) = self.panic_handler.lock().unwrap().as_ref() {
+                            handler(payload);
+                        }
+                    }
+                }
+                Some(Job::Terminate) => break,
+                None => thread::yield_now(),
+            }
+        }
+
+        let (lock, cvar) = &*done;
+        let mut slot = lock.lock().unwrap();
+        let rb = loop {
+            if let Some(rb) = slot.take() {
+                break rb;
+            }
+            slot = cvar.wait(slot).unwrap();
+        };
+
+        match a_result {
+            Ok(ra) => (ra, rb),
+            Err(payload) => panic::resume_unwind(payload),
+        }
+    }
+
+    /// Steal one job from the global injector or from any worker's local
+    /// queue, used by `join` to help drain the pool while waiting.
+    ///
+    /// Never returns another worker's `Job::Terminate` shutdown sentinel:
+    /// that job only exists so `run()` eventually stops and `Drop::drop`'s
+    /// `job.wait()` can return, so silently consuming it here would wedge
+    /// that worker (and pool teardown) forever. If one turns up mid-steal
+    /// it's handed straight back to its owner instead.
+    fn steal_any(&self) -> Option<Job> {
+        loop {
+            match self.injector.steal() {
+                Steal::Success(job) => return Some(job),
+                Steal::Empty => break,
+                Steal::Retry => continue,
+            }
+        }
+        for worker in self.workers.read().unwrap().iter() {
+            loop {
+                match worker.stealer().steal() {
+                    Steal::Success(Job::Terminate) => {
+                        worker.push(Job::Terminate);
+                        break;
+                    }
+                    Steal::Success(job) => return Some(job),
+                    Steal::Empty => break,
+                    Steal::Retry => continue,
+                }
+            }
+        }
+        None
+    }
+
+    /// Schedule `f` to run exactly once on every worker thread, passing it
+    /// the worker's index, and block until all of them have finished.
+    ///
+    /// Useful for per-thread setup/teardown (seeding thread-local RNGs,
+    /// opening per-thread file handles, warming caches) that `par_for`
+    /// can't express since that distributes by item, not by thread.
+    pub fn broadcast<F>(&self, f: F)
+    where
+        F: Fn(usize) + Send + Sync + 'static,
+    {
+        self.broadcast_with_result(move |id| f(id));
+    }
+
+    /// Like `broadcast`, but collects the return value of `f` from every
+    /// worker into a `Vec` ordered by worker index.
+    pub fn broadcast_with_result<F, R>(&self, f: F) -> Vec<R>
+    where
+        F: Fn(usize) -> R + Send + Sync + 'static,
+        R: Send + 'static,
+    {
+        let workers = self.workers.read().unwrap();
+        let num_workers = workers.len();
+        let f = Arc::new(f);
+        let countdown = Arc::new(AtomicUsize::new(num_workers));
+        let done = Arc::new((Mutex::new(()), Condvar::new()));
+        let results: Vec<Mutex<Option<R>>> = (0..num_workers).map(|_| Mutex::new(None)).collect();
+        let results = Arc::new(results);
+
+        for (id, worker) in workers.iter().enumerate() {
+            let f = Arc::clone(&f);
+            let countdown = Arc::clone(&countdown);
+            let done = Arc::clone(&done);
+            let results = Arc::clone(&results);
+
+            worker.set_broadcast_job(Box::new(move || {
+                *results[id].lock().unwrap() = Some(f(id));
+                if countdown.fetch_sub(1, Ordering::AcqRel) == 1 {
+                    let (lock, cvar) = &*done;
+                    let _guard = lock.lock().unwrap();
+                    cvar.notify_all();
+                }
+            }));
+        }
+        // Broadcast jobs sit in a dedicated per-worker slot rather than the
+        // injector, so simply wake every parked worker to have them notice it.
+        self.sleep.notify_all();
+
+        let (lock, cvar) = &*done;
+        let guard = lock.lock().unwrap();
+        let _guard = cvar
+            .wait_while(guard, |_| countdown.load(Ordering::Acquire) != 0)
+            .unwrap();
+
+        Arc::try_unwrap(results)
+            .unwrap_or_else(|_| unreachable!("no worker keeps a reference after completion"))
+            .into_iter()
+            .map(|cell| cell.into_inner().unwrap().expect("broadcast job did not run"))
+            .collect()
+    }
+
     /// Applies in parallel the function `f` on a iterable object `iter`.
     ///
     /// # Examples
@@ -285,6 +946,106 @@ impl ThreadPool {
             iter.into_iter().for_each(|el| s.execute(move || (f)(el)));
         });
     }
+
+    /// Parallel stencil update: computes every cell of `dst` from the
+    /// full, immutable `src` grid via `f(src, row, col)`.
+    ///
+    /// Reading a cell's neighbors while writing it is awkward with
+    /// `par_for`, which hands out `&mut` elements one at a time — the
+    /// borrow checker won't let `f` also read from the rest of the same
+    /// buffer. `par_stencil` sidesteps this by keeping `src` and `dst`
+    /// separate: `dst` is partitioned into contiguous row-chunks, one per
+    /// worker, so each worker writes a disjoint slice while reading `src`
+    /// freely, with no aliasing conflict.
+    ///
+    /// `width` is the number of columns in the grid; `src` and `dst` must
+    /// be the same length, a multiple of `width`. Edge-cell handling (e.g.
+    /// clamping or wrapping neighbor lookups at the grid boundary) is the
+    /// caller's responsibility inside `f`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pspp::thread_pool::ThreadPool;
+    ///
+    /// let mut tp = ThreadPool::new_with_global_registry(8);
+    /// let width = 4;
+    /// let src = vec![1i32; width * 4];
+    /// let mut dst = vec![0i32; width * 4];
+    ///
+    /// tp.par_stencil(&src, &mut dst, width, |grid, row, col| {
+    ///     grid[row * width + col] + 1
+    /// });
+    /// assert_eq!(dst, vec![2i32; width * 4]);
+    /// ```
+    pub fn par_stencil<T, F>(&mut self, src: &[T], dst: &mut [T], width: usize, f: F)
+    where
+        T: Send + Sync,
+        F: Fn(&[T], usize, usize) -> T + Sync,
+    {
+        assert_eq!(src.len(), dst.len(), "src and dst must be the same length");
+        assert_eq!(
+            src.len() % width,
+            0,
+            "grid length must be a multiple of width"
+        );
+        let height = src.len() / width;
+        let num_workers = self.workers.read().unwrap().len().max(1);
+        let rows_per_chunk = (height + num_workers - 1) / num_workers;
+
+        self.scoped(|s| {
+            let mut remaining = dst;
+            let mut row_start = 0;
+            while !remaining.is_empty() {
+                let take_rows = rows_per_chunk.min(height - row_start);
+                let (chunk, rest) = remaining.split_at_mut(take_rows * width);
+                let f = &f;
+                s.execute(move || {
+                    for (i, cell) in chunk.iter_mut().enumerate() {
+                        let row = row_start + i / width;
+                        let col = i % width;
+                        *cell = f(src, row, col);
+                    }
+                });
+                remaining = rest;
+                row_start += take_rows;
+            }
+        });
+        self.wait();
+    }
+
+    /// Like `par_stencil`, but runs `iters` sweeps back to back, swapping
+    /// `buf_a` and `buf_b` between the source and destination role each
+    /// time so the caller doesn't have to juggle buffers manually — each
+    /// sweep reads the previous one's output and writes over the
+    /// now-stale buffer from two sweeps ago.
+    ///
+    /// Returns `true` if the final result ended up in `buf_b` (i.e.
+    /// `iters` is odd), so the caller knows which buffer to read next.
+    pub fn par_stencil_iter<T, F>(
+        &mut self,
+        buf_a: &mut [T],
+        buf_b: &mut [T],
+        width: usize,
+        iters: usize,
+        f: F,
+    ) -> bool
+    where
+        T: Send + Sync,
+        F: Fn(&[T], usize, usize) -> T + Sync,
+    {
+        let mut src_is_a = true;
+        for _ in 0..iters {
+            if src_is_a {
+                self.par_stencil(buf_a, buf_b, width, &f);
+            } else {
+                self.par_stencil(buf_b, buf_a, width, &f);
+            }
+            src_is_a = !src_is_a;
+        }
+        !src_is_a
+    }
+
     /// Applies in parallel the function `f` on a iterable object `iter`,
     /// producing a new iterator with the results.
     ///
@@ -336,6 +1097,154 @@ impl ThreadPool {
         unordered_map.into_values()
     }
 
+    /// Like `par_map`, but returns an iterator that yields results in
+    /// input order as they complete, rather than waiting for every element
+    /// to finish and buffering the whole result set first.
+    ///
+    /// Out-of-order completions are held in a small reorder buffer and
+    /// released once their turn comes; each `next()` call blocks on the
+    /// result channel itself (a real blocking receive, not a spin loop)
+    /// rather than polling `wait()`, so the caller only ever waits for the
+    /// next in-order element instead of the whole batch. This bounds
+    /// latency to the first result and lets a downstream consumer overlap
+    /// with ongoing computation — useful when `par_map` feeds a pipeline
+    /// or farm stage rather than being collected in one go.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pspp::thread_pool::ThreadPool;
+    ///
+    /// let mut pool = ThreadPool::new_with_global_registry(8);
+    /// let res: Vec<u64> = pool.par_map_streaming(0..8, |n| n * n).collect();
+    /// assert_eq!(res, vec![0, 1, 4, 9, 16, 25, 36, 49]);
+    /// ```
+    pub fn par_map_streaming<Iter: IntoIterator, F, R>(
+        &mut self,
+        iter: Iter,
+        f: F,
+    ) -> ParMapStream<R>
+    where
+        F: FnOnce(Iter::Item) -> R + Send + Copy,
+        <Iter as IntoIterator>::Item: Send,
+        R: Send + 'static,
+    {
+        let (rx, tx) = Channel::channel(true);
+        let arc_tx = Arc::new(tx);
+        let mut total = 0usize;
+        self.scoped(|s| {
+            iter.into_iter().enumerate().for_each(|el| {
+                total += 1;
+                let cp = Arc::clone(&arc_tx);
+                s.execute(move || {
+                    let err = cp.send((el.0, f(el.1)));
+                    if err.is_err() {
+                        panic!("Error: {}", err.unwrap_err());
+                    }
+                });
+            });
+        });
+        ParMapStream {
+            rx,
+            buffer: BTreeMap::new(),
+            next_expected: 0,
+            remaining: total,
+        }
+    }
+
+    /// Associative fold over `data` down to a single accumulator, for the
+    /// large class of reductions (sum, max, merged histogram, ...) that
+    /// don't fit a key-grouped map-reduce: `identity()` produces a fresh
+    /// per-worker accumulator, `fold` folds items from that worker's chunk
+    /// into it, and `combine` merges two accumulators into one.
+    ///
+    /// Each worker folds its chunk sequentially starting from
+    /// `identity()`; the resulting partial accumulators are then merged
+    /// pairwise in a balanced, log-depth tree via `combine`. The caller
+    /// must guarantee `combine` is associative and that `identity()` is
+    /// its neutral element — the result is deterministic in value, but the
+    /// order in which chunks are combined is unspecified.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pspp::thread_pool::ThreadPool;
+    ///
+    /// let mut tp = ThreadPool::new_with_global_registry(8);
+    /// let sum = tp.par_fold(0..1000, || 0i32, |acc, x| acc + x, |a, b| a + b);
+    /// assert_eq!(sum, (0..1000).sum::<i32>());
+    /// ```
+    pub fn par_fold<T, A, ID, F, C>(
+        &mut self,
+        data: impl IntoIterator<Item = T>,
+        identity: ID,
+        fold: F,
+        combine: C,
+    ) -> A
+    where
+        T: Send,
+        A: Send + 'static,
+        ID: Fn() -> A + Sync,
+        F: Fn(A, T) -> A + Sync,
+        C: Fn(A, A) -> A,
+    {
+        let mut items: Vec<T> = data.into_iter().collect();
+        if items.is_empty() {
+            return identity();
+        }
+
+        let num_workers = self.workers.read().unwrap().len().max(1);
+        let chunk_size = (items.len() + num_workers - 1) / num_workers;
+        let mut chunks = Vec::new();
+        while !items.is_empty() {
+            let take = chunk_size.min(items.len());
+            let rest = items.split_off(take);
+            chunks.push(items);
+            items = rest;
+        }
+        let num_chunks = chunks.len();
+
+        let (rx, tx) = Channel::channel(true);
+        let arc_tx = Arc::new(tx);
+        self.scoped(|s| {
+            for chunk in chunks {
+                let cp = Arc::clone(&arc_tx);
+                let identity = &identity;
+                let fold = &fold;
+                s.execute(move || {
+                    let acc = chunk.into_iter().fold(identity(), |a, t| fold(a, t));
+                    let err = cp.send(acc);
+                    if err.is_err() {
+                        panic!("Error: {}", err.unwrap_err());
+                    }
+                });
+            }
+        });
+        self.wait();
+
+        let mut partials = Vec::with_capacity(num_chunks);
+        while partials.len() < num_chunks {
+            match rx.receive() {
+                Ok(Some(acc)) => partials.push(acc),
+                Ok(None) => continue,
+                Err(e) => panic!("Error: {}", e),
+            }
+        }
+
+        while partials.len() > 1 {
+            let mut next = Vec::with_capacity((partials.len() + 1) / 2);
+            let mut iter = partials.into_iter();
+            while let Some(a) = iter.next() {
+                next.push(match iter.next() {
+                    Some(b) => combine(a, b),
+                    None => a,
+                });
+            }
+            partials = next;
+        }
+        partials.into_iter().next().unwrap_or_else(identity)
+    }
+
     /// Borrows the thread pool and allows executing jobs on other
     /// threads during that scope via the argument of the closure.
     pub fn scoped<'pool, 'scope, F, R>(&'pool mut self, f: F) -> R
@@ -353,19 +1262,54 @@ impl ThreadPool {
 impl Drop for ThreadPool {
     fn drop(&mut self) {
         trace!("Closing threadpool");
-        for worker in &self.workers {
+        let workers = self.workers.read().unwrap();
+        for worker in workers.iter() {
             worker.clean_stealers();
         }
 
-        for worker in &self.workers {
+        for worker in workers.iter() {
             worker.push(Job::Terminate);
         }
+        self.sleep.notify_all();
 
         for job in &self.jobs_info {
             job.wait();
         }
     }
 }
+
+/// Iterator returned by `ThreadPool::par_map_streaming`; see its docs.
+pub struct ParMapStream<R> {
+    rx: Box<dyn Receiver<(usize, R)> + Sync + Send>,
+    buffer: BTreeMap<usize, R>,
+    next_expected: usize,
+    remaining: usize,
+}
+
+impl<R> Iterator for ParMapStream<R> {
+    type Item = R;
+
+    fn next(&mut self) -> Option<R> {
+        if self.remaining == 0 {
+            return None;
+        }
+        loop {
+            if let Some(result) = self.buffer.remove(&self.next_expected) {
+                self.next_expected += 1;
+                self.remaining -= 1;
+                return Some(result);
+            }
+            match self.rx.receive() {
+                Ok(Some((order, result))) => {
+                    self.buffer.insert(order, result);
+                }
+                Ok(None) => continue,
+                Err(e) => panic!("Error: {}", e),
+            }
+        }
+    }
+}
+
 /// A scope to executes scoped jobs in the thread pool.
 pub struct Scope<'pool, 'scope> {
     pool: &'pool mut ThreadPool,
@@ -380,9 +1324,20 @@ impl<'pool, 'scope> Scope<'pool, 'scope> {
     {
         let task = unsafe { mem::transmute::<Func<'scope>, Func<'static>>(Box::new(task)) };
         self.pool.injector.push(Job::NewJob(Box::new(task)));
-        self.pool
-            .total_tasks
-            .fetch_add(1, std::sync::atomic::Ordering::AcqRel);
+        self.pool.total_tasks.fetch_add(1, Ordering::AcqRel);
+        self.pool.sleep.notify_one();
+    }
+
+    /// Like `ThreadPool::broadcast`, but accepts a closure borrowing data
+    /// from the scope instead of requiring `'static`.
+    pub fn broadcast<F>(&self, f: F)
+    where
+        F: Fn(usize) + Send + Sync + 'scope,
+    {
+        type BroadcastFunc<'a> = Box<dyn Fn(usize) + Send + Sync + 'a>;
+        let f: BroadcastFunc<'static> =
+            unsafe { mem::transmute::<BroadcastFunc<'scope>, BroadcastFunc<'static>>(Box::new(f)) };
+        self.pool.broadcast(move |id| f(id));
     }
 }
 
@@ -486,4 +1441,133 @@ mod tests {
         }
         Orchestrator::delete_global_orchestrator();
     }
+
+    #[test]
+    #[serial]
+    fn test_threadpool_idle_then_wakes() {
+        let tp = ThreadPool::new_with_global_registry(4);
+        tp.wait();
+        std::thread::sleep(std::time::Duration::from_millis(50));
+
+        let counter = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter_cp = counter.clone();
+        tp.execute(move || {
+            counter_cp.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        });
+        tp.wait();
+        Orchestrator::delete_global_orchestrator();
+        assert_eq!(counter.load(std::sync::atomic::Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    #[serial]
+    fn test_join() {
+        let tp = ThreadPool::new_with_global_registry(4);
+        let (a, b) = tp.join(|| fib(10), || fib(15));
+        Orchestrator::delete_global_orchestrator();
+        assert_eq!(a, 55);
+        assert_eq!(b, 610);
+    }
+
+    #[test]
+    #[serial]
+    fn test_join_recursive_sum() {
+        fn sum(slice: &[i32], tp: &ThreadPool) -> i32 {
+            if slice.len() <= 2 {
+                return slice.iter().sum();
+            }
+            let mid = slice.len() / 2;
+            let (left, right) = slice.split_at(mid);
+            let (a, b) = tp.join(|| sum(left, tp), || sum(right, tp));
+            a + b
+        }
+
+        let tp = ThreadPool::new_with_global_registry(4);
+        let vec: Vec<i32> = (1..=1000).collect();
+        let total = sum(&vec, &tp);
+        Orchestrator::delete_global_orchestrator();
+        assert_eq!(total, 500_500);
+    }
+
+    #[test]
+    #[serial]
+    fn test_broadcast_runs_once_per_worker() {
+        let tp = ThreadPool::new_with_global_registry(8);
+        let mut res = tp.broadcast_with_result(|id| id * 2);
+        res.sort_unstable();
+        Orchestrator::delete_global_orchestrator();
+        assert_eq!(res, vec![0, 2, 4, 6, 8, 10, 12, 14]);
+    }
+
+    #[test]
+    #[serial]
+    fn test_broadcast_touches_every_worker() {
+        let tp = ThreadPool::new_with_global_registry(4);
+        let touched = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+
+        tp.broadcast({
+            let touched = touched.clone();
+            move |_id| {
+                touched.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            }
+        });
+
+        Orchestrator::delete_global_orchestrator();
+        assert_eq!(touched.load(std::sync::atomic::Ordering::SeqCst), 4);
+    }
+
+    #[test]
+    #[serial]
+    fn test_panicking_job_does_not_hang_wait() {
+        let caught = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let caught_cp = caught.clone();
+        let tp = ThreadPool::new_with_global_registry(4)
+            .with_panic_handler(move |_payload| {
+                caught_cp.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            });
+
+        tp.execute(|| panic!("boom"));
+        tp.wait();
+
+        let completed = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let completed_cp = completed.clone();
+        tp.execute(move || {
+            completed_cp.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        });
+        tp.wait();
+
+        Orchestrator::delete_global_orchestrator();
+        assert_eq!(caught.load(std::sync::atomic::Ordering::SeqCst), 1);
+        assert_eq!(completed.load(std::sync::atomic::Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    #[serial]
+    fn test_builder_with_custom_fairness_still_completes_all_jobs() {
+        let tp = ThreadPool::builder(4)
+            .fairness_jobs(1)
+            .fairness_interval(std::time::Duration::from_micros(1))
+            .build();
+
+        let counter = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        for _ in 0..200 {
+            let counter_cp = counter.clone();
+            tp.execute(move || {
+                counter_cp.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            });
+        }
+        tp.wait();
+
+        Orchestrator::delete_global_orchestrator();
+        assert_eq!(counter.load(std::sync::atomic::Ordering::SeqCst), 200);
+    }
+
+    #[test]
+    #[serial]
+    fn test_par_map_streaming_preserves_order() {
+        let mut tp = ThreadPool::new_with_global_registry(8);
+        let res: Vec<i32> = tp.par_map_streaming(0..200, |n| n * 2).collect();
+        Orchestrator::delete_global_orchestrator();
+        assert_eq!(res, (0..200).map(|n| n * 2).collect::<Vec<i32>>());
+    }
 }